@@ -0,0 +1,181 @@
+use super::{
+    block_cache_sync_all, get_block_cache, Bitmap, BlockDevice, DiskInode, DiskInodeType, Inode,
+    SuperBlock, BLOCK_SZ,
+};
+use alloc::{collections::BTreeMap, sync::Arc};
+use core::mem;
+use spin::Mutex;
+
+type DataBlock = [u8; BLOCK_SZ];
+
+pub struct EasyFileSystem {
+    pub block_device: Arc<dyn BlockDevice>,
+    pub inode_bitmap: Bitmap,
+    pub data_bitmap: Bitmap,
+    inode_area_start_block: u32,
+    data_area_start_block: u32,
+    data_area_blocks: u32,
+    /// Number of `Inode`s in memory for each inode id.
+    open_inodes: BTreeMap<u32, usize>,
+}
+
+/// Layout on disk:
+/// SuperBlock | inode bitmap | inode area | data bitmap | data area
+impl EasyFileSystem {
+    pub fn create(
+        block_device: Arc<dyn BlockDevice>,
+        total_blocks: u32,
+        inode_bitmap_blocks: u32,
+    ) -> Arc<Mutex<Self>> {
+        // calculate block size of areas & create bitmaps
+        let inode_bitmap = Bitmap::new(1, inode_bitmap_blocks as usize);
+        let inode_num = inode_bitmap.maximum();
+        let inode_area_blocks =
+            ((inode_num * mem::size_of::<DiskInode>() + BLOCK_SZ - 1) / BLOCK_SZ) as u32;
+        let inode_total_blocks = inode_bitmap_blocks + inode_area_blocks;
+        let data_total_blocks = total_blocks - 1 - inode_total_blocks;
+        let data_bitmap_blocks = (data_total_blocks + 4096) / 4097;
+        let data_area_blocks = data_total_blocks - data_bitmap_blocks;
+        let data_bitmap = Bitmap::new(
+            (1 + inode_total_blocks) as usize,
+            data_bitmap_blocks as usize,
+        );
+        let mut efs = Self {
+            block_device: block_device.clone(),
+            inode_bitmap,
+            data_bitmap,
+            inode_area_start_block: 1 + inode_bitmap_blocks,
+            data_area_start_block: 1 + inode_total_blocks + data_bitmap_blocks,
+            data_area_blocks,
+            open_inodes: BTreeMap::new(),
+        };
+        // clear all blocks
+        for i in 0..total_blocks {
+            get_block_cache(i as usize, block_device.clone())
+                .lock()
+                .modify(0, |data_block: &mut DataBlock| {
+                    data_block.iter_mut().for_each(|byte| *byte = 0);
+                });
+        }
+        // initialize SuperBlock
+        get_block_cache(0, block_device.clone()).lock().modify(
+            0,
+            |super_block: &mut SuperBlock| {
+                super_block.initialize(
+                    total_blocks,
+                    inode_bitmap_blocks,
+                    inode_area_blocks,
+                    data_bitmap_blocks,
+                    data_area_blocks,
+                );
+            },
+        );
+        // create the root directory, whose inode id is 0 and is its own parent
+        assert_eq!(efs.alloc_inode(), Some(0));
+        let efs = Arc::new(Mutex::new(efs));
+        let root_inode = Self::root_inode(&efs);
+        assert!(root_inode.initialize(DiskInodeType::Directory, 0, &mut efs.lock()));
+        block_cache_sync_all();
+        efs
+    }
+
+    pub fn open(block_device: Arc<dyn BlockDevice>) -> Arc<Mutex<Self>> {
+        // read SuperBlock
+        get_block_cache(0, block_device.clone())
+            .lock()
+            .read(0, |super_block: &SuperBlock| {
+                assert!(super_block.is_valid(), "Error loading EFS!");
+                let inode_total_blocks =
+                    super_block.inode_bitmap_blocks + super_block.inode_area_blocks;
+                let efs = Self {
+                    block_device,
+                    inode_bitmap: Bitmap::new(1, super_block.inode_bitmap_blocks as usize),
+                    data_bitmap: Bitmap::new(
+                        (1 + inode_total_blocks) as usize,
+                        super_block.data_bitmap_blocks as usize,
+                    ),
+                    inode_area_start_block: 1 + super_block.inode_bitmap_blocks,
+                    data_area_start_block: 1 + inode_total_blocks + super_block.data_bitmap_blocks,
+                    data_area_blocks: super_block.data_area_blocks,
+                    open_inodes: BTreeMap::new(),
+                };
+                Arc::new(Mutex::new(efs))
+            })
+    }
+
+    pub fn root_inode(efs: &Arc<Mutex<Self>>) -> Inode {
+        let mut fs = efs.lock();
+        let block_device = fs.block_device.clone();
+        let (block_id, block_offset) = fs.get_disk_inode_pos(0);
+        fs.open_inode(0);
+        Inode::new(0, block_id, block_offset, efs.clone(), block_device)
+    }
+
+    /// Count an `Inode` created for `inode_id`.
+    pub(crate) fn open_inode(&mut self, inode_id: u32) {
+        *self.open_inodes.entry(inode_id).or_insert(0) += 1;
+    }
+
+    /// Count an `Inode` dropped, return true if it is the last one for `inode_id`.
+    pub(crate) fn close_inode(&mut self, inode_id: u32) -> bool {
+        let count = self.open_inodes.get_mut(&inode_id).unwrap();
+        *count -= 1;
+        if *count == 0 {
+            self.open_inodes.remove(&inode_id);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Return (block_id, offset) of the DiskInode.
+    pub fn get_disk_inode_pos(&self, inode_id: u32) -> (u32, usize) {
+        let inode_size = mem::size_of::<DiskInode>();
+        let inodes_per_block = (BLOCK_SZ / inode_size) as u32;
+        let block_id = self.inode_area_start_block + inode_id / inodes_per_block;
+        (
+            block_id,
+            (inode_id % inodes_per_block) as usize * inode_size,
+        )
+    }
+
+    pub fn get_data_block_id(&self, data_block_id: u32) -> u32 {
+        self.data_area_start_block + data_block_id
+    }
+
+    /// Return None if all inodes are in use.
+    pub fn alloc_inode(&mut self) -> Option<u32> {
+        self.inode_bitmap
+            .alloc(&self.block_device)
+            .map(|inode_id| inode_id as u32)
+    }
+
+    pub fn dealloc_inode(&mut self, inode_id: u32) {
+        self.inode_bitmap
+            .dealloc(&self.block_device, inode_id as usize)
+    }
+
+    /// Return a block id, not a bit id in data bitmap, or None if the disk is full.
+    pub fn alloc_data(&mut self) -> Option<u32> {
+        let bit = self.data_bitmap.alloc(&self.block_device)?;
+        // the bitmap may have more bits than blocks in the data area
+        if bit >= self.data_area_blocks as usize {
+            self.data_bitmap.dealloc(&self.block_device, bit);
+            return None;
+        }
+        Some(bit as u32 + self.data_area_start_block)
+    }
+
+    pub fn dealloc_data(&mut self, block_id: u32) {
+        // keep free blocks zeroed, so that newly allocated blocks need no cleaning
+        get_block_cache(block_id as usize, self.block_device.clone())
+            .lock()
+            .modify(0, |data_block: &mut DataBlock| {
+                data_block.iter_mut().for_each(|byte| *byte = 0);
+            });
+        self.data_bitmap.dealloc(
+            &self.block_device,
+            (block_id - self.data_area_start_block) as usize,
+        )
+    }
+}
@@ -0,0 +1,364 @@
+use super::{
+    block_cache_sync_all, get_block_cache, BlockDevice, DirEntry, DiskInode, DiskInodeType,
+    EasyFileSystem, DIRENT_SZ,
+};
+use alloc::{string::String, sync::Arc, vec::Vec};
+use spin::{Mutex, MutexGuard};
+
+/// An inode in memory, which only records the position of its DiskInode.
+pub struct Inode {
+    inode_id: u32,
+    block_id: usize,
+    block_offset: usize,
+    fs: Arc<Mutex<EasyFileSystem>>,
+    block_device: Arc<dyn BlockDevice>,
+}
+
+impl Inode {
+    /// We should not acquire efs lock here, the caller counts it by `open_inode`.
+    pub(crate) fn new(
+        inode_id: u32,
+        block_id: u32,
+        block_offset: usize,
+        fs: Arc<Mutex<EasyFileSystem>>,
+        block_device: Arc<dyn BlockDevice>,
+    ) -> Self {
+        Self {
+            inode_id,
+            block_id: block_id as usize,
+            block_offset,
+            fs,
+            block_device,
+        }
+    }
+
+    fn read_disk_inode<V>(&self, f: impl FnOnce(&DiskInode) -> V) -> V {
+        get_block_cache(self.block_id, self.block_device.clone())
+            .lock()
+            .read(self.block_offset, f)
+    }
+
+    fn modify_disk_inode<V>(&self, f: impl FnOnce(&mut DiskInode) -> V) -> V {
+        get_block_cache(self.block_id, self.block_device.clone())
+            .lock()
+            .modify(self.block_offset, f)
+    }
+
+    /// Return (slot, inode id) of the dirent named `name`.
+    fn find_dirent(&self, name: &str, disk_inode: &DiskInode) -> Option<(usize, u32)> {
+        // assert it is a directory
+        assert!(disk_inode.is_dir());
+        let file_count = disk_inode.size as usize / DIRENT_SZ;
+        let mut dirent = DirEntry::empty();
+        for i in 0..file_count {
+            assert_eq!(
+                disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device),
+                DIRENT_SZ,
+            );
+            if !dirent.is_empty() && dirent.name() == name {
+                return Some((i, dirent.inode_number()));
+            }
+        }
+        None
+    }
+
+    fn find_inode_id(&self, name: &str, disk_inode: &DiskInode) -> Option<u32> {
+        self.find_dirent(name, disk_inode)
+            .map(|(_, inode_id)| inode_id)
+    }
+
+    fn inode_with_id(&self, fs: &mut MutexGuard<EasyFileSystem>, inode_id: u32) -> Arc<Inode> {
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        fs.open_inode(inode_id);
+        Arc::new(Self::new(
+            inode_id,
+            block_id,
+            block_offset,
+            self.fs.clone(),
+            self.block_device.clone(),
+        ))
+    }
+
+    pub fn inode_id(&self) -> u32 {
+        self.inode_id
+    }
+
+    pub fn is_dir(&self) -> bool {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.is_dir())
+    }
+
+    /// Find a file under current directory by name.
+    pub fn find(&self, name: &str) -> Option<Arc<Inode>> {
+        let mut fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| self.find_inode_id(name, disk_inode))
+            .map(|inode_id| self.inode_with_id(&mut fs, inode_id))
+    }
+
+    /// Return false if there are not enough free blocks, leaving the size as it is.
+    fn increase_size(
+        &self,
+        new_size: u32,
+        disk_inode: &mut DiskInode,
+        fs: &mut MutexGuard<EasyFileSystem>,
+    ) -> bool {
+        if new_size < disk_inode.size {
+            return true;
+        }
+        let blocks_needed = disk_inode.blocks_num_needed(new_size);
+        let mut v: Vec<u32> = Vec::new();
+        for _ in 0..blocks_needed {
+            match fs.alloc_data() {
+                Some(block_id) => v.push(block_id),
+                None => {
+                    for block_id in v {
+                        fs.dealloc_data(block_id);
+                    }
+                    return false;
+                }
+            }
+        }
+        disk_inode.increase_size(new_size, v, &self.block_device);
+        true
+    }
+
+    fn clear_data(&self, disk_inode: &mut DiskInode, fs: &mut MutexGuard<EasyFileSystem>) {
+        let size = disk_inode.size;
+        let data_blocks_dealloc = disk_inode.clear_size(&self.block_device);
+        assert_eq!(
+            data_blocks_dealloc.len(),
+            DiskInode::total_blocks(size) as usize
+        );
+        for data_block in data_blocks_dealloc.into_iter() {
+            fs.dealloc_data(data_block);
+        }
+    }
+
+    /// Put a dirent into the first free slot, or append it to the directory,
+    /// return false if the directory cannot grow.
+    fn add_dirent(
+        &self,
+        name: &str,
+        inode_id: u32,
+        disk_inode: &mut DiskInode,
+        fs: &mut MutexGuard<EasyFileSystem>,
+    ) -> bool {
+        let file_count = disk_inode.size as usize / DIRENT_SZ;
+        let mut dirent = DirEntry::empty();
+        let slot = (0..file_count)
+            .find(|&i| {
+                disk_inode.read_at(i * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device);
+                dirent.is_empty()
+            })
+            .unwrap_or(file_count);
+        if slot == file_count
+            && !self.increase_size(((file_count + 1) * DIRENT_SZ) as u32, disk_inode, fs)
+        {
+            return false;
+        }
+        let dirent = DirEntry::new(name, inode_id);
+        disk_inode.write_at(slot * DIRENT_SZ, dirent.as_bytes(), &self.block_device);
+        true
+    }
+
+    /// Initialize the DiskInode, a directory gets its `.` and `..` entries.
+    /// Return false if there is no room for them.
+    pub(crate) fn initialize(
+        &self,
+        type_: DiskInodeType,
+        parent_id: u32,
+        fs: &mut MutexGuard<EasyFileSystem>,
+    ) -> bool {
+        self.modify_disk_inode(|disk_inode| {
+            disk_inode.initialize(type_);
+            !disk_inode.is_dir()
+                || self.add_dirent(".", self.inode_id, disk_inode, fs)
+                    && self.add_dirent("..", parent_id, disk_inode, fs)
+        })
+    }
+
+    fn create_inode(&self, name: &str, type_: DiskInodeType) -> Option<Arc<Inode>> {
+        let mut fs = self.fs.lock();
+        if self
+            .read_disk_inode(|disk_inode| self.find_inode_id(name, disk_inode))
+            .is_some()
+        {
+            return None;
+        }
+        // alloc and initialize a inode
+        let new_inode_id = fs.alloc_inode()?;
+        let inode = self.inode_with_id(&mut fs, new_inode_id);
+        let is_dir = type_ == DiskInodeType::Directory;
+        let added = inode.initialize(type_, self.inode_id, &mut fs)
+            && self.modify_disk_inode(|dir_inode| {
+                self.add_dirent(name, new_inode_id, dir_inode, &mut fs)
+            });
+        if !added {
+            // the new inode is released when dropped without the lock
+            inode.modify_disk_inode(|disk_inode| disk_inode.nlink = 0);
+            drop(fs);
+            return None;
+        }
+        // linked by `..` of the new directory
+        if is_dir {
+            self.modify_disk_inode(|dir_inode| dir_inode.nlink += 1);
+        }
+        block_cache_sync_all();
+        Some(inode)
+    }
+
+    /// Create a regular file under current directory,
+    /// return None if it exists or there is no space left.
+    pub fn create(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::File)
+    }
+
+    /// Create a directory under current directory,
+    /// return None if it exists or there is no space left.
+    pub fn create_dir(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::Directory)
+    }
+
+    /// Create a hard link named `name` to `inode` under current directory,
+    /// return false if it exists or there is no space left.
+    pub fn link(&self, name: &str, inode: &Inode) -> bool {
+        let mut fs = self.fs.lock();
+        if self
+            .read_disk_inode(|disk_inode| self.find_inode_id(name, disk_inode))
+            .is_some()
+        {
+            return false;
+        }
+        let added = self.modify_disk_inode(|dir_inode| {
+            self.add_dirent(name, inode.inode_id, dir_inode, &mut fs)
+        });
+        if !added {
+            return false;
+        }
+        inode.modify_disk_inode(|disk_inode| disk_inode.nlink += 1);
+        block_cache_sync_all();
+        true
+    }
+
+    /// Remove `name` under current directory, a directory must be emptied before.
+    /// The inode is released once it loses its last link and no `Inode` refers to it.
+    pub fn unlink(&self, name: &str) -> bool {
+        let mut fs = self.fs.lock();
+        let (slot, inode_id) =
+            match self.read_disk_inode(|disk_inode| self.find_dirent(name, disk_inode)) {
+                Some(dirent) => dirent,
+                None => return false,
+            };
+        let inode = self.inode_with_id(&mut fs, inode_id);
+        let is_dir = inode.read_disk_inode(|disk_inode| disk_inode.is_dir());
+        self.modify_disk_inode(|dir_inode| {
+            dir_inode.write_at(
+                slot * DIRENT_SZ,
+                DirEntry::empty().as_bytes(),
+                &self.block_device,
+            );
+            if is_dir {
+                dir_inode.nlink -= 1;
+            }
+        });
+        inode.modify_disk_inode(|disk_inode| {
+            // a directory loses its `.` as well
+            disk_inode.nlink -= if is_dir { 2 } else { 1 };
+        });
+        block_cache_sync_all();
+        // the inode may be released on dropping, which needs the lock
+        drop(fs);
+        true
+    }
+
+    /// List names of all files under current directory.
+    pub fn ls(&self) -> Vec<String> {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| {
+            let file_count = disk_inode.size as usize / DIRENT_SZ;
+            let mut v: Vec<String> = Vec::new();
+            for i in 0..file_count {
+                let mut dirent = DirEntry::empty();
+                assert_eq!(
+                    disk_inode.read_at(i * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device),
+                    DIRENT_SZ,
+                );
+                if !dirent.is_empty() {
+                    v.push(String::from(dirent.name()));
+                }
+            }
+            v
+        })
+    }
+
+    /// Read the first entry at or after `offset` under current directory,
+    /// return the offset of the entry next to it, its name and inode.
+    pub fn read_dirent(&self, offset: usize) -> Option<(usize, String, Arc<Inode>)> {
+        let mut fs = self.fs.lock();
+        let (next, name, inode_id) = self.read_disk_inode(|disk_inode| {
+            let mut offset = offset;
+            let mut dirent = DirEntry::empty();
+            while offset + DIRENT_SZ <= disk_inode.size as usize {
+                disk_inode.read_at(offset, dirent.as_bytes_mut(), &self.block_device);
+                offset += DIRENT_SZ;
+                if !dirent.is_empty() {
+                    return Some((offset, String::from(dirent.name()), dirent.inode_number()));
+                }
+            }
+            None
+        })?;
+        Some((next, name, self.inode_with_id(&mut fs, inode_id)))
+    }
+
+    pub fn size(&self) -> usize {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.size as usize)
+    }
+
+    pub fn nlink(&self) -> u32 {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.nlink)
+    }
+
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.read_at(offset, buf, &self.block_device))
+    }
+
+    /// The file grows automatically if writing beyond its end, if there is no space
+    /// left for it, only bytes within the current size are written.
+    pub fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let mut fs = self.fs.lock();
+        let size = self.modify_disk_inode(|disk_inode| {
+            if !self.increase_size((offset + buf.len()) as u32, disk_inode, &mut fs)
+                && offset >= disk_inode.size as usize
+            {
+                return 0;
+            }
+            disk_inode.write_at(offset, buf, &self.block_device)
+        });
+        block_cache_sync_all();
+        size
+    }
+
+    /// Truncate the file to zero length and release its data blocks.
+    pub fn clear(&self) {
+        let mut fs = self.fs.lock();
+        self.modify_disk_inode(|disk_inode| self.clear_data(disk_inode, &mut fs));
+        block_cache_sync_all();
+    }
+}
+
+impl Drop for Inode {
+    /// Release the inode if it has been unlinked and this is the last `Inode` of it,
+    /// so that files stay usable while they are open.
+    fn drop(&mut self) {
+        let mut fs = self.fs.lock();
+        if fs.close_inode(self.inode_id) && self.read_disk_inode(|disk_inode| disk_inode.nlink) == 0
+        {
+            self.modify_disk_inode(|disk_inode| self.clear_data(disk_inode, &mut fs));
+            fs.dealloc_inode(self.inode_id);
+            block_cache_sync_all();
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use easy_fs::{BlockDevice, EasyFileSystem, BLOCK_SZ};
+use std::env;
+use std::fs::{read_dir, File, OpenOptions};
+use std::io::{Read, Result, Seek, SeekFrom, Write};
+use std::process;
+use std::sync::{Arc, Mutex};
+
+/// 16 MiB image
+const FS_TOTAL_BLOCKS: u32 = 32768;
+const FS_INODE_BITMAP_BLOCKS: u32 = 1;
+
+struct BlockFile(Mutex<File>);
+
+impl BlockDevice for BlockFile {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        let mut file = self.0.lock().unwrap();
+        file.seek(SeekFrom::Start((block_id * BLOCK_SZ) as u64))
+            .expect("Error when seeking!");
+        assert_eq!(file.read(buf).unwrap(), BLOCK_SZ, "Not a complete block!");
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        let mut file = self.0.lock().unwrap();
+        file.seek(SeekFrom::Start((block_id * BLOCK_SZ) as u64))
+            .expect("Error when seeking!");
+        assert_eq!(file.write(buf).unwrap(), BLOCK_SZ, "Not a complete block!");
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("Usage: fs-pack -s <app source dir> -t <app target dir>");
+    process::exit(1);
+}
+
+fn main() {
+    let mut src_path = None;
+    let mut target_path = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-s" | "--source" => src_path = args.next(),
+            "-t" | "--target" => target_path = args.next(),
+            _ => usage(),
+        }
+    }
+    match (src_path, target_path) {
+        (Some(src_path), Some(target_path)) => pack(&src_path, &target_path).unwrap(),
+        _ => usage(),
+    }
+}
+
+/// Create `fs.img` under `target_path`, containing every app whose source
+/// file lies in `src_path`.
+fn pack(src_path: &str, target_path: &str) -> Result<()> {
+    let block_file = Arc::new(BlockFile(Mutex::new({
+        let f = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(format!("{}{}", target_path, "fs.img"))?;
+        f.set_len((FS_TOTAL_BLOCKS as usize * BLOCK_SZ) as u64)?;
+        f
+    })));
+    let efs = EasyFileSystem::create(block_file, FS_TOTAL_BLOCKS, FS_INODE_BITMAP_BLOCKS);
+    let root_inode = Arc::new(EasyFileSystem::root_inode(&efs));
+    // apps are named after their source files, files without an extension are skipped
+    let mut apps: Vec<_> = read_dir(src_path)?
+        .filter_map(|dir_entry| {
+            let mut name_with_ext = dir_entry.unwrap().file_name().into_string().unwrap();
+            let ext_start = name_with_ext.find('.')?;
+            name_with_ext.truncate(ext_start);
+            Some(name_with_ext)
+        })
+        .collect();
+    apps.sort();
+    for app in apps {
+        // load app data from host file system
+        let mut host_file = File::open(format!("{}{}", target_path, app))?;
+        let mut all_data: Vec<u8> = Vec::new();
+        host_file.read_to_end(&mut all_data)?;
+        // create a file in easy-fs
+        let inode = root_inode.create(app.as_str()).unwrap();
+        // write data to easy-fs
+        inode.write_at(0, all_data.as_slice());
+        println!("{}: {} bytes", app, all_data.len());
+    }
+    Ok(())
+}
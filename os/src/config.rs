@@ -1,9 +1,21 @@
 pub const CLOCK_FREQ: usize = 12500000;
 pub const USER_STACK_SIZE: usize = 4096 * 2;
+/// The user stack grows down from the top of the lower half of Sv39 address space.
+pub const USER_STACK_TOP: usize = 0x40_0000_0000;
+/// Max size the user stack may grow to on page faults.
+pub const USER_STACK_LIMIT: usize = 4096 * 16;
 pub const KERNEL_STACK_SIZE: usize = 4096 * 2;
 pub const KERNEL_HEAP_SIZE: usize = 1024 * 512;
 pub const PAGE_SIZE: usize = 1 << PAGE_SIZE_BITS;
 pub const PAGE_SIZE_BITS: usize = 12;
 pub const MEMORY_END: usize = 0x80800000;
+/// Size of the swap device, which should match the swap image made in Makefile.
+pub const SWAP_SIZE: usize = 16 * 1024 * 1024;
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 pub const TRAP_CONTEXT: usize = TRAMPOLINE - PAGE_SIZE;
+pub const MAX_FD_NUM: usize = 256;
+/// Max total size of the arguments and environment pushed onto a new user stack.
+pub const ARG_MAX: usize = 4096;
+
+/// MMIO regions of devices on the QEMU virt board, as (start, len).
+pub const MMIO: &[(usize, usize)] = &[(0x10001000, 0x1000), (0x10002000, 0x1000)];
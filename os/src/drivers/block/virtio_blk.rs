@@ -0,0 +1,84 @@
+use crate::mm::{frame_alloc, FrameTracker, PageTable, PhysAddr, PhysPageNum, StepByOne, VirtAddr};
+use alloc::vec::Vec;
+use easy_fs::BlockDevice;
+use lazy_static::lazy_static;
+use riscv::register::satp;
+use spin::Mutex;
+use virtio_drivers::{VirtIOBlk, VirtIOHeader};
+
+pub const VIRTIO0: usize = 0x10001000;
+pub const VIRTIO1: usize = 0x10002000;
+
+pub struct VirtIOBlock(Mutex<VirtIOBlk<'static>>);
+
+impl VirtIOBlock {
+    pub fn new(base: usize) -> Self {
+        Self(Mutex::new(
+            VirtIOBlk::new(unsafe { &mut *(base as *mut VirtIOHeader) }).unwrap(),
+        ))
+    }
+}
+
+impl BlockDevice for VirtIOBlock {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        self.0
+            .lock()
+            .read_block(block_id, buf)
+            .expect("Error when reading VirtIOBlk");
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        self.0
+            .lock()
+            .write_block(block_id, buf)
+            .expect("Error when writing VirtIOBlk");
+    }
+}
+
+lazy_static! {
+    /// Frames used by virtqueues, which should live as long as the device.
+    static ref QUEUE_FRAMES: Mutex<Vec<FrameTracker>> = Mutex::new(Vec::new());
+}
+
+/// The virtio driver requires physically contiguous pages.
+#[no_mangle]
+pub extern "C" fn virtio_dma_alloc(pages: usize) -> PhysAddr {
+    let mut queue_frames = QUEUE_FRAMES.lock();
+    let mut ppn_base = PhysPageNum(0);
+    for i in 0..pages {
+        let frame = frame_alloc().unwrap();
+        if i == 0 {
+            ppn_base = frame.ppn;
+        }
+        assert_eq!(frame.ppn.0, ppn_base.0 + i);
+        queue_frames.push(frame);
+    }
+    ppn_base.into()
+}
+
+#[no_mangle]
+pub extern "C" fn virtio_dma_dealloc(pa: PhysAddr, pages: usize) -> i32 {
+    let mut ppn: PhysPageNum = pa.into();
+    let mut queue_frames = QUEUE_FRAMES.lock();
+    for _ in 0..pages {
+        // dropping the FrameTracker deallocates the frame
+        queue_frames.retain(|frame| frame.ppn != ppn);
+        ppn.step();
+    }
+    0
+}
+
+/// Physical memory is identically mapped in kernel space.
+#[no_mangle]
+pub extern "C" fn virtio_phys_to_virt(pa: PhysAddr) -> VirtAddr {
+    VirtAddr(pa.0)
+}
+
+/// Buffers may be on kernel stacks, which are not identically mapped.
+/// KERNEL_SPACE is not locked here, as pages may be swapped out while it is locked.
+#[no_mangle]
+pub extern "C" fn virtio_virt_to_phys(va: VirtAddr) -> PhysAddr {
+    PageTable::from_token(satp::read().bits())
+        .translate_va(va)
+        .unwrap()
+}
@@ -0,0 +1,413 @@
+use super::{File, Stat, StatMode, SEEK_CUR, SEEK_END, SEEK_SET};
+use crate::{
+    drivers::BLOCK_DEVICE,
+    errno::{
+        EBUSY, EEXIST, EFAULT, EFBIG, EINVAL, EISDIR, ENAMETOOLONG, ENOENT, ENOSPC, ENOTDIR,
+        ENOTEMPTY, EPERM,
+    },
+    mm::UserBuffer,
+};
+use alloc::{string::String, sync::Arc, vec::Vec};
+use easy_fs::{EasyFileSystem, Inode, MAX_FILE_SIZE, NAME_LENGTH_LIMIT};
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+/// A file opened by a process, which keeps its own offset.
+pub struct OSInode {
+    readable: bool,
+    writable: bool,
+    append: bool,
+    inner: Mutex<OSInodeInner>,
+}
+
+struct OSInodeInner {
+    offset: usize,
+    inode: Arc<Inode>,
+}
+
+impl OSInode {
+    pub fn new(flags: OpenFlags, inode: Arc<Inode>) -> Self {
+        let (readable, writable) = flags.read_write();
+        Self {
+            readable,
+            writable,
+            append: flags.contains(OpenFlags::APPEND),
+            inner: Mutex::new(OSInodeInner { offset: 0, inode }),
+        }
+    }
+
+    pub fn inode(&self) -> Arc<Inode> {
+        self.inner.lock().inode.clone()
+    }
+
+    /// Pack entries from current offset as `linux_dirent64` records, which take up no more
+    /// than `len` bytes, and hand them to `copy_out`. The offset moves past them only if
+    /// they are copied, otherwise EFAULT is returned.
+    pub fn read_dirents(
+        &self,
+        len: usize,
+        copy_out: impl FnOnce(&[u8]) -> Option<usize>,
+    ) -> Result<usize, isize> {
+        let mut inner = self.inner.lock();
+        if !inner.inode.is_dir() {
+            return Err(ENOTDIR);
+        }
+        let mut v: Vec<u8> = Vec::new();
+        let mut offset = inner.offset;
+        while let Some((next, name, inode)) = inner.inode.read_dirent(offset) {
+            // d_ino, d_off, d_reclen, d_type and d_name ending with '\0', aligned to 8
+            let reclen = (8 + 8 + 2 + 1 + name.len() + 1 + 7) & !7;
+            if v.len() + reclen > len {
+                if v.is_empty() {
+                    return Err(EINVAL);
+                }
+                break;
+            }
+            let end = v.len() + reclen;
+            v.extend_from_slice(&(inode.inode_id() as u64).to_le_bytes());
+            v.extend_from_slice(&(next as i64).to_le_bytes());
+            v.extend_from_slice(&(reclen as u16).to_le_bytes());
+            v.push(if inode.is_dir() { DT_DIR } else { DT_REG });
+            v.extend_from_slice(name.as_bytes());
+            v.resize(end, 0);
+            offset = next;
+        }
+        let copied = copy_out(&v).ok_or(EFAULT)?;
+        inner.offset = offset;
+        Ok(copied)
+    }
+
+    /// Read from current offset to the end of file.
+    pub fn read_all(&self) -> Vec<u8> {
+        let mut inner = self.inner.lock();
+        let mut buffer = [0u8; 512];
+        let mut v: Vec<u8> = Vec::new();
+        loop {
+            let len = inner.inode.read_at(inner.offset, &mut buffer);
+            if len == 0 {
+                break;
+            }
+            inner.offset += len;
+            v.extend_from_slice(&buffer[..len]);
+        }
+        v
+    }
+}
+
+lazy_static! {
+    pub static ref ROOT_INODE: Arc<Inode> = {
+        let efs = EasyFileSystem::open(BLOCK_DEVICE.clone());
+        Arc::new(EasyFileSystem::root_inode(&efs))
+    };
+}
+
+const DT_DIR: u8 = 4;
+const DT_REG: u8 = 8;
+
+pub fn list_apps() {
+    println!("/**** APPS ****");
+    for app in ROOT_INODE.ls() {
+        if app != "." && app != ".." {
+            println!("{}", app);
+        }
+    }
+    println!("**************/")
+}
+
+bitflags! {
+    pub struct OpenFlags: u32 {
+        const RDONLY = 0;
+        const WRONLY = 1 << 0;
+        const RDWR = 1 << 1;
+        const CREAT = 1 << 6;
+        const EXCL = 1 << 7;
+        const TRUNC = 1 << 9;
+        const APPEND = 1 << 10;
+        const DIRECTORY = 1 << 16;
+        const CLOEXEC = 1 << 19;
+    }
+}
+
+impl OpenFlags {
+    /// WRONLY and RDWR are exclusive.
+    pub fn is_valid(&self) -> bool {
+        !self.contains(Self::WRONLY | Self::RDWR)
+    }
+
+    /// Return (readable, writable).
+    fn read_write(&self) -> (bool, bool) {
+        if self.contains(Self::WRONLY) {
+            (false, true)
+        } else if self.contains(Self::RDWR) {
+            (true, true)
+        } else {
+            (true, false)
+        }
+    }
+}
+
+fn find_child(dir: &Arc<Inode>, name: &str) -> Result<Arc<Inode>, isize> {
+    if !dir.is_dir() {
+        return Err(ENOTDIR);
+    }
+    if name.len() > NAME_LENGTH_LIMIT {
+        return Err(ENAMETOOLONG);
+    }
+    dir.find(name).ok_or(ENOENT)
+}
+
+/// Walk along `path` from `dir`, or from the root directory if it is absolute.
+pub fn find_inode(dir: &Arc<Inode>, path: &str) -> Result<Arc<Inode>, isize> {
+    let mut inode = if path.starts_with('/') {
+        ROOT_INODE.clone()
+    } else {
+        dir.clone()
+    };
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        inode = find_child(&inode, name)?;
+    }
+    Ok(inode)
+}
+
+/// Join `path` to the absolute `cwd` by names, resolving `.` and `..` on the way.
+pub fn join_path(cwd: &str, path: &str) -> String {
+    let mut names: Vec<&str> = Vec::new();
+    if !path.starts_with('/') {
+        names.extend(cwd.split('/').filter(|name| !name.is_empty()));
+    }
+    for name in path.split('/') {
+        match name {
+            "" | "." => {}
+            ".." => {
+                names.pop();
+            }
+            _ => names.push(name),
+        }
+    }
+    if names.is_empty() {
+        return String::from("/");
+    }
+    names
+        .iter()
+        .fold(String::new(), |path, name| path + "/" + name)
+}
+
+/// Split `path` into the directory containing it and its last name,
+/// which is empty if `path` refers to the root directory.
+fn find_parent<'a>(dir: &Arc<Inode>, path: &'a str) -> Result<(Arc<Inode>, &'a str), isize> {
+    if path.is_empty() {
+        return Err(ENOENT);
+    }
+    let path = path.trim_end_matches('/');
+    let (parent, name) = match path.rfind('/') {
+        Some(pos) => (&path[..=pos], &path[pos + 1..]),
+        None => ("", path),
+    };
+    let parent = find_inode(dir, parent)?;
+    if !parent.is_dir() {
+        return Err(ENOTDIR);
+    }
+    if name.len() > NAME_LENGTH_LIMIT {
+        return Err(ENAMETOOLONG);
+    }
+    Ok((parent, name))
+}
+
+/// Open a file relative to `dir`, return an errno on failure.
+pub fn open_file_at(dir: &Arc<Inode>, path: &str, flags: OpenFlags) -> Result<Arc<OSInode>, isize> {
+    let (parent, name) = find_parent(dir, path)?;
+    let inode = if let Ok(inode) = find_inode(&parent, name) {
+        if flags.contains(OpenFlags::CREAT | OpenFlags::EXCL) {
+            return Err(EEXIST);
+        }
+        if inode.is_dir() {
+            if flags.read_write().1 {
+                return Err(EISDIR);
+            }
+        } else if flags.contains(OpenFlags::DIRECTORY) {
+            return Err(ENOTDIR);
+        }
+        if flags.contains(OpenFlags::TRUNC) && flags.read_write().1 {
+            inode.clear();
+        }
+        inode
+    } else if flags.contains(OpenFlags::CREAT) {
+        // it does not exist, so there is no space left if it cannot be created
+        parent.create(name).ok_or(ENOSPC)?
+    } else {
+        return Err(ENOENT);
+    };
+    Ok(Arc::new(OSInode::new(flags, inode)))
+}
+
+/// Open a file relative to the root directory.
+pub fn open_file(path: &str, flags: OpenFlags) -> Result<Arc<OSInode>, isize> {
+    open_file_at(&ROOT_INODE, path, flags)
+}
+
+pub fn make_dir_at(dir: &Arc<Inode>, path: &str) -> Result<(), isize> {
+    let (parent, name) = find_parent(dir, path)?;
+    if name.is_empty() || parent.find(name).is_some() {
+        return Err(EEXIST);
+    }
+    parent.create_dir(name).ok_or(ENOSPC)?;
+    Ok(())
+}
+
+/// Remove a file, or an empty directory if `rmdir` is set.
+pub fn unlink_at(dir: &Arc<Inode>, path: &str, rmdir: bool) -> Result<(), isize> {
+    let (parent, name) = find_parent(dir, path)?;
+    match name {
+        "" => return Err(EBUSY),
+        "." => return Err(EINVAL),
+        ".." => return Err(ENOTEMPTY),
+        _ => {}
+    }
+    let inode = find_child(&parent, name)?;
+    if inode.is_dir() {
+        if !rmdir {
+            return Err(EISDIR);
+        }
+        if inode.ls().iter().any(|name| name != "." && name != "..") {
+            return Err(ENOTEMPTY);
+        }
+    } else if rmdir {
+        return Err(ENOTDIR);
+    }
+    parent.unlink(name);
+    Ok(())
+}
+
+/// Make a hard link to a regular file.
+pub fn link_at(
+    old_dir: &Arc<Inode>,
+    old_path: &str,
+    new_dir: &Arc<Inode>,
+    new_path: &str,
+) -> Result<(), isize> {
+    if old_path.is_empty() {
+        return Err(ENOENT);
+    }
+    let inode = find_inode(old_dir, old_path)?;
+    if inode.is_dir() {
+        return Err(EPERM);
+    }
+    let (parent, name) = find_parent(new_dir, new_path)?;
+    if name.is_empty() || parent.find(name).is_some() {
+        return Err(EEXIST);
+    }
+    if !parent.link(name, &inode) {
+        return Err(ENOSPC);
+    }
+    Ok(())
+}
+
+/// Read from `offset` without moving the offset of any opened file.
+fn read_inode(inode: &Inode, offset: usize, mut buf: UserBuffer) -> usize {
+    let mut total_read_size = 0usize;
+    for slice in buf.buffers.iter_mut() {
+        let read_size = inode.read_at(offset + total_read_size, *slice);
+        total_read_size += read_size;
+        if read_size < slice.len() {
+            break;
+        }
+    }
+    total_read_size
+}
+
+/// Write at `offset`, bytes beyond the maximum file size are dropped.
+/// Return EFBIG if `offset` is at the maximum size already, or ENOSPC if nothing can be
+/// written as the disk is full.
+fn write_inode(inode: &Inode, offset: usize, buf: UserBuffer) -> Result<usize, isize> {
+    if offset >= MAX_FILE_SIZE && buf.len() > 0 {
+        return Err(EFBIG);
+    }
+    let mut total_write_size = 0usize;
+    for slice in buf.buffers.iter() {
+        let start = offset + total_write_size;
+        let len = slice.len().min(MAX_FILE_SIZE.saturating_sub(start));
+        if len == 0 {
+            break;
+        }
+        let write_size = inode.write_at(start, &slice[..len]);
+        total_write_size += write_size;
+        if write_size < len && total_write_size == 0 {
+            return Err(ENOSPC);
+        }
+        if write_size < slice.len() {
+            break;
+        }
+    }
+    Ok(total_write_size)
+}
+
+impl File for OSInode {
+    fn as_os_inode(&self) -> Option<&OSInode> {
+        Some(self)
+    }
+
+    fn readable(&self) -> bool {
+        self.readable
+    }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
+
+    fn read(&self, buf: UserBuffer) -> usize {
+        let mut inner = self.inner.lock();
+        let read_size = read_inode(&inner.inode, inner.offset, buf);
+        inner.offset += read_size;
+        read_size
+    }
+
+    fn write(&self, buf: UserBuffer) -> Result<usize, isize> {
+        let mut inner = self.inner.lock();
+        if self.append {
+            inner.offset = inner.inode.size();
+        }
+        let write_size = write_inode(&inner.inode, inner.offset, buf)?;
+        inner.offset += write_size;
+        Ok(write_size)
+    }
+
+    fn read_at(&self, offset: usize, buf: UserBuffer) -> Result<usize, isize> {
+        Ok(read_inode(&self.inode(), offset, buf))
+    }
+
+    fn write_at(&self, offset: usize, buf: UserBuffer) -> Result<usize, isize> {
+        write_inode(&self.inode(), offset, buf)
+    }
+
+    /// Seeking beyond the end is allowed, and a later write leaves a hole of zeros.
+    fn seek(&self, offset: isize, whence: usize) -> Result<usize, isize> {
+        let mut inner = self.inner.lock();
+        let base = match whence {
+            SEEK_SET => 0,
+            SEEK_CUR => inner.offset,
+            SEEK_END => inner.inode.size(),
+            _ => return Err(EINVAL),
+        };
+        let offset = (base as isize).checked_add(offset).ok_or(EINVAL)?;
+        if offset < 0 {
+            return Err(EINVAL);
+        }
+        inner.offset = offset as usize;
+        Ok(inner.offset)
+    }
+
+    fn stat(&self) -> Stat {
+        let inode = self.inode();
+        Stat {
+            dev: 0,
+            ino: inode.inode_id() as u64,
+            mode: if inode.is_dir() {
+                StatMode::DIR
+            } else {
+                StatMode::FILE
+            },
+            nlink: inode.nlink(),
+            size: inode.size() as u64,
+        }
+    }
+}
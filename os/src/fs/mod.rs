@@ -1,11 +1,84 @@
+mod inode;
 mod pipe;
 mod stdio;
 
-use crate::mm::UserBuffer;
+use crate::{errno::ESPIPE, mm::UserBuffer};
+use alloc::sync::Arc;
+use core::{mem, slice};
+pub use inode::{
+    find_inode, join_path, link_at, list_apps, make_dir_at, open_file, open_file_at, unlink_at,
+    OSInode, OpenFlags, ROOT_INODE,
+};
 pub use pipe::make_pipe;
-pub use stdio::{STDIN, STDOUT};
+pub use stdio::{poll_stdin, STDIN, STDOUT};
+
+pub const SEEK_SET: usize = 0;
+pub const SEEK_CUR: usize = 1;
+pub const SEEK_END: usize = 2;
 
 pub trait File: Send + Sync {
+    fn readable(&self) -> bool;
+    fn writable(&self) -> bool;
     fn read(&self, buf: UserBuffer) -> usize;
-    fn write(&self, buf: UserBuffer) -> usize;
+    /// Return the bytes written, or an errno if nothing can be written.
+    fn write(&self, buf: UserBuffer) -> Result<usize, isize>;
+    /// Read at `offset` without moving the file offset.
+    fn read_at(&self, _offset: usize, _buf: UserBuffer) -> Result<usize, isize> {
+        Err(ESPIPE)
+    }
+    /// Write at `offset` without moving the file offset.
+    fn write_at(&self, _offset: usize, _buf: UserBuffer) -> Result<usize, isize> {
+        Err(ESPIPE)
+    }
+    /// Return the new offset from the beginning of the file.
+    fn seek(&self, _offset: isize, _whence: usize) -> Result<usize, isize> {
+        Err(ESPIPE)
+    }
+    fn stat(&self) -> Stat;
+    /// Only files on disk are backed by an inode.
+    fn as_os_inode(&self) -> Option<&OSInode> {
+        None
+    }
+}
+
+/// An entry of the fd table.
+#[derive(Clone)]
+pub struct FileDescriptor {
+    pub file: Arc<dyn File>,
+    /// Closed automatically on exec.
+    pub cloexec: bool,
+}
+
+impl FileDescriptor {
+    pub fn new(file: Arc<dyn File>, cloexec: bool) -> Self {
+        Self { file, cloexec }
+    }
+}
+
+/// All files live on the one disk, so `dev` is always 0.
+#[repr(C)]
+pub struct Stat {
+    pub dev: u64,
+    pub ino: u64,
+    pub mode: StatMode,
+    pub nlink: u32,
+    pub size: u64,
+}
+
+impl Stat {
+    pub fn as_bytes(&self) -> &[u8] {
+        let len = mem::size_of::<Stat>();
+        let data = self as *const _ as usize as *const u8;
+        unsafe { slice::from_raw_parts(data, len) }
+    }
+}
+
+bitflags! {
+    /// File type bits, the same as `S_IFMT` ones.
+    pub struct StatMode: u32 {
+        const FIFO = 0o010000;
+        const CHR = 0o020000;
+        const DIR = 0o040000;
+        const FILE = 0o100000;
+    }
 }
@@ -1,5 +1,11 @@
-use super::File;
-use crate::{mm::UserBuffer, task::suspend_current_and_run_next};
+use super::{File, Stat, StatMode};
+use crate::{
+    errno::EPIPE,
+    mm::UserBuffer,
+    task::{
+        block_current_and_run_next, current_killed, current_send_signal, SignalFlags, WaitQueue,
+    },
+};
 use alloc::sync::{Arc, Weak};
 use spin::Mutex;
 
@@ -41,7 +47,12 @@ struct PipeRingBuffer {
     head: usize,
     tail: usize,
     status: RingBufferStatus,
+    read_end: Option<Weak<Pipe>>,
     write_end: Option<Weak<Pipe>>,
+    /// Readers wait here for bytes or all write ends closed.
+    readers: WaitQueue,
+    /// Writers wait here for room or all read ends closed.
+    writers: WaitQueue,
 }
 
 impl PipeRingBuffer {
@@ -51,10 +62,17 @@ impl PipeRingBuffer {
             head: 0,
             tail: 0,
             status: RingBufferStatus::EMPTY,
+            read_end: None,
             write_end: None,
+            readers: WaitQueue::new(),
+            writers: WaitQueue::new(),
         }
     }
 
+    fn set_read_end(&mut self, read_end: &Arc<Pipe>) {
+        self.read_end = Some(Arc::downgrade(read_end));
+    }
+
     fn set_write_end(&mut self, write_end: &Arc<Pipe>) {
         self.write_end = Some(Arc::downgrade(write_end));
     }
@@ -94,21 +112,48 @@ impl PipeRingBuffer {
         RING_BUFFER_SIZE - self.available_read()
     }
 
+    fn all_read_ends_closed(&self) -> bool {
+        self.read_end.as_ref().unwrap().upgrade().is_none()
+    }
+
     fn all_write_ends_closed(&self) -> bool {
         self.write_end.as_ref().unwrap().upgrade().is_none()
     }
 }
 
+impl Drop for Pipe {
+    /// Readers waiting for bytes should see the end of the pipe once the write end is closed,
+    /// and writers waiting for room should see the broken pipe once the read end is closed.
+    fn drop(&mut self) {
+        let mut ring_buffer = self.buffer.lock();
+        if self.writable {
+            ring_buffer.readers.wake_all();
+        }
+        if self.readable {
+            ring_buffer.writers.wake_all();
+        }
+    }
+}
+
 /// Return (read_end, write_end)
 pub fn make_pipe() -> (Arc<Pipe>, Arc<Pipe>) {
     let buffer = Arc::new(Mutex::new(PipeRingBuffer::new()));
     let read_end = Arc::new(Pipe::read_end_with_buffer(buffer.clone()));
     let write_end = Arc::new(Pipe::write_end_with_buffer(buffer.clone()));
+    buffer.lock().set_read_end(&read_end);
     buffer.lock().set_write_end(&write_end);
     (read_end, write_end)
 }
 
 impl File for Pipe {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
+
     fn read(&self, buf: UserBuffer) -> usize {
         assert!(self.readable);
         let mut buf_iter = buf.into_iter();
@@ -117,13 +162,17 @@ impl File for Pipe {
             let mut ring_buffer = self.buffer.lock();
             let loop_read = ring_buffer.available_read();
             if loop_read == 0 {
-                if ring_buffer.all_write_ends_closed() {
+                // a killed process should not wait for writers which may never come
+                if ring_buffer.all_write_ends_closed() || current_killed() {
                     return read_size;
                 }
+                ring_buffer.readers.add_current();
                 drop(ring_buffer);
-                suspend_current_and_run_next();
+                block_current_and_run_next();
                 continue;
             }
+            // there will be room for writers
+            ring_buffer.writers.wake_all();
             // read at most loop_read bytes
             for _ in 0..loop_read {
                 if let Some(byte_ref) = buf_iter.next() {
@@ -138,27 +187,53 @@ impl File for Pipe {
         }
     }
 
-    fn write(&self, buf: UserBuffer) -> usize {
+    fn write(&self, buf: UserBuffer) -> Result<usize, isize> {
         assert!(self.writable);
         let mut buf_iter = buf.into_iter();
         let mut write_size = 0;
         loop {
             let mut ring_buffer = self.buffer.lock();
+            // bytes written would never be read
+            if ring_buffer.all_read_ends_closed() {
+                drop(ring_buffer);
+                current_send_signal(SignalFlags::SIGPIPE);
+                return if write_size > 0 {
+                    Ok(write_size)
+                } else {
+                    Err(EPIPE)
+                };
+            }
             let loop_write = ring_buffer.available_write();
             if loop_write == 0 {
+                if current_killed() {
+                    return Ok(write_size);
+                }
+                ring_buffer.writers.add_current();
                 drop(ring_buffer);
-                suspend_current_and_run_next();
+                block_current_and_run_next();
                 continue;
             }
+            // there will be bytes for readers
+            ring_buffer.readers.wake_all();
             // write at most loop_write bytes
             for _ in 0..loop_write {
                 if let Some(byte_ref) = buf_iter.next() {
                     ring_buffer.write_byte(unsafe { *byte_ref });
                     write_size += 1;
                 } else {
-                    return write_size;
+                    return Ok(write_size);
                 }
             }
         }
     }
+
+    fn stat(&self) -> Stat {
+        Stat {
+            dev: 0,
+            ino: 0,
+            mode: StatMode::FIFO,
+            nlink: 1,
+            size: 0,
+        }
+    }
 }
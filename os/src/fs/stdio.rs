@@ -1,43 +1,116 @@
-use super::File;
+use super::{File, Stat, StatMode};
 use crate::mm::UserBuffer;
 use crate::sbi::{console_getchar, console_putchar};
-use crate::task::suspend_current_and_run_next;
+use crate::task::{block_current_and_run_next, current_killed, WaitQueue};
+use alloc::collections::VecDeque;
+use lazy_static::lazy_static;
+use spin::Mutex;
 
 pub struct STDIN;
 
+struct StdinInner {
+    /// Input read by `poll_stdin` but not by any reader yet.
+    chars: VecDeque<u8>,
+    readers: WaitQueue,
+}
+
+lazy_static! {
+    static ref STDIN_INNER: Mutex<StdinInner> = Mutex::new(StdinInner {
+        chars: VecDeque::new(),
+        readers: WaitQueue::new(),
+    });
+}
+
+/// The console raises no interrupt, so it is polled here for readers waiting.
+pub fn poll_stdin() {
+    let mut inner = STDIN_INNER.lock();
+    if inner.readers.is_empty() {
+        return;
+    }
+    let c = console_getchar();
+    if c != 0 {
+        inner.chars.push_back(c as u8);
+        inner.readers.wake_all();
+    }
+}
+
 impl File for STDIN {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
     fn read(&self, mut buf: UserBuffer) -> usize {
         assert_eq!(buf.len(), 1, "Only support len = 1 in sys_read!");
         let ch = loop {
-            let c = console_getchar();
-            if c == 0 {
-                suspend_current_and_run_next();
-            } else {
+            let mut inner = STDIN_INNER.lock();
+            if let Some(c) = inner.chars.pop_front() {
                 break c;
             }
-        } as u8;
+            let c = console_getchar();
+            if c != 0 {
+                break c as u8;
+            }
+            if current_killed() {
+                return 0;
+            }
+            inner.readers.add_current();
+            drop(inner);
+            block_current_and_run_next();
+        };
         buf.buffers[0][0] = ch;
         1
     }
 
-    fn write(&self, _buf: UserBuffer) -> usize {
+    fn write(&self, _buf: UserBuffer) -> Result<usize, isize> {
         panic!("Cannot write to stdin!");
     }
+
+    fn stat(&self) -> Stat {
+        Stat {
+            dev: 0,
+            ino: 0,
+            mode: StatMode::CHR,
+            nlink: 1,
+            size: 0,
+        }
+    }
 }
 
 pub struct STDOUT;
 
 impl File for STDOUT {
+    fn readable(&self) -> bool {
+        false
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
     fn read(&self, _buf: UserBuffer) -> usize {
         panic!("Cannot read from stdout!");
     }
 
-    fn write(&self, buf: UserBuffer) -> usize {
+    fn write(&self, buf: UserBuffer) -> Result<usize, isize> {
         for buffer in &buf.buffers {
             for &c in buffer.iter() {
                 console_putchar(c as usize);
             }
         }
-        buf.len()
+        Ok(buf.len())
+    }
+
+    fn stat(&self) -> Stat {
+        Stat {
+            dev: 0,
+            ino: 0,
+            mode: StatMode::CHR,
+            nlink: 1,
+            size: 0,
+        }
     }
 }
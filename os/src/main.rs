@@ -15,9 +15,10 @@ extern crate alloc;
 #[macro_use]
 mod console;
 mod config;
+mod drivers;
+mod errno;
 mod fs;
 mod lang_items;
-mod loader;
 mod logging;
 mod mm;
 mod sbi;
@@ -27,7 +28,6 @@ mod timer;
 mod trap;
 
 global_asm!(include_str!("entry.asm"));
-global_asm!(include_str!("link_app.S"));
 
 #[no_mangle]
 pub fn rust_main() -> ! {
@@ -37,7 +37,7 @@ pub fn rust_main() -> ! {
     mm::init();
     trap::init();
     trap::enable_timer_interrupt();
-    loader::list_apps();
+    fs::list_apps();
     task::add_initproc();
     task::run_tasks();
     panic!("Unreachable in rust_main!");
@@ -6,9 +6,11 @@ use core::{
 };
 use derive_more::{From, Into};
 
+#[repr(C)]
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, From, Into)]
 pub struct PhysAddr(pub usize);
 
+#[repr(C)]
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, From, Into)]
 pub struct VirtAddr(pub usize);
 
@@ -133,6 +135,11 @@ impl StepByOne for VirtPageNum {
         self.0 += 1;
     }
 }
+impl StepByOne for PhysPageNum {
+    fn step(&mut self) {
+        self.0 += 1;
+    }
+}
 
 #[derive(Clone, Copy, PartialEq)]
 pub struct SimpleRange<T>
@@ -160,6 +167,10 @@ where
     pub fn overlap(&self, range: &SimpleRange<T>) -> bool {
         self.l < range.r && range.l < self.r
     }
+
+    pub fn contains(&self, value: T) -> bool {
+        self.l <= value && value < self.r
+    }
 }
 impl<T> IntoIterator for SimpleRange<T>
 where
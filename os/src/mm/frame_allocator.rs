@@ -1,4 +1,5 @@
 use super::address::{PhysAddr, PhysPageNum};
+use super::swap::swap_out;
 use crate::config::MEMORY_END;
 use alloc::vec::Vec;
 use core::fmt::{self, Debug, Formatter};
@@ -101,10 +102,12 @@ pub fn init_frame_allocator() {
 }
 
 pub fn frame_alloc() -> Option<FrameTracker> {
-    FRAME_ALLOCATOR
-        .lock()
-        .alloc()
-        .map(|ppn| FrameTracker::new(ppn))
+    let mut ppn = FRAME_ALLOCATOR.lock().alloc();
+    // swap out a user page to make room if frames run out
+    if ppn.is_none() && swap_out() {
+        ppn = FRAME_ALLOCATOR.lock().alloc();
+    }
+    ppn.map(|ppn| FrameTracker::new(ppn))
 }
 
 fn frame_dealloc(ppn: PhysPageNum) {
@@ -1,14 +1,33 @@
 use super::{
     address::{PhysAddr, PhysPageNum, StepByOne, VPNRange, VirtAddr, VirtPageNum},
-    frame_allocator::{frame_alloc, FrameTracker},
+    frame_allocator::frame_alloc,
     page_table::{PTEFlags, PageTable},
+    swap::Page,
     PageTableEntry,
 };
-use crate::config::{MEMORY_END, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT, USER_STACK_SIZE};
-use alloc::{collections::BTreeMap, vec::Vec};
+use crate::config::{
+    MEMORY_END, MMIO, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT, USER_STACK_LIMIT, USER_STACK_SIZE,
+    USER_STACK_TOP,
+};
+use alloc::{collections::BTreeMap, sync::Arc, vec, vec::Vec};
+use core::mem;
+use easy_fs::Inode;
 use lazy_static::lazy_static;
 use riscv::register::satp;
 use spin::Mutex;
+use xmas_elf::{header::Class, program::ProgramHeader64};
+
+/// Types of entries in the auxiliary vector.
+pub const AT_NULL: usize = 0;
+pub const AT_PHDR: usize = 3;
+pub const AT_PHENT: usize = 4;
+pub const AT_PHNUM: usize = 5;
+pub const AT_PAGESZ: usize = 6;
+pub const AT_ENTRY: usize = 9;
+pub const AT_RANDOM: usize = 25;
+
+/// Entries of the auxiliary vector as (type, value).
+type Auxv = Vec<(usize, usize)>;
 
 extern "C" {
     fn stext();
@@ -29,6 +48,14 @@ enum MapType {
     Framed,
 }
 
+/// The kind of access which causes a page fault.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum AccessType {
+    Read,
+    Write,
+    Execute,
+}
+
 bitflags! {
     pub struct MapPermission: u8 {
         const R = 1 << 1;
@@ -40,9 +67,25 @@ bitflags! {
 
 struct MapArea {
     vpn_range: VPNRange,
-    data_frames: BTreeMap<VirtPageNum, FrameTracker>,
+    /// A page may be shared with other spaces by copy-on-write after fork,
+    /// and swapped out if the area is in user space.
+    data_frames: BTreeMap<VirtPageNum, Arc<Page>>,
     map_type: MapType,
     map_perm: MapPermission,
+    /// Frames of a lazy area are allocated on first touch rather than when it is mapped.
+    lazy: bool,
+    /// Pages of a shared area stay writable in children after fork, instead of being
+    /// copied on write.
+    shared: bool,
+    /// Pages are read from the file on first touch, and written back if the area is shared.
+    file: Option<MappedFile>,
+}
+
+/// A file mapped to an area, which starts at `offset` of the file.
+#[derive(Clone)]
+struct MappedFile {
+    inode: Arc<Inode>,
+    offset: usize,
 }
 
 impl MapArea {
@@ -59,41 +102,85 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_type,
             map_perm,
+            lazy: false,
+            shared: false,
+            file: None,
         }
     }
 
     fn from_another(another: &Self) -> Self {
         Self {
+            vpn_range: another.vpn_range,
             data_frames: BTreeMap::new(),
-            ..*another
+            map_type: another.map_type,
+            map_perm: another.map_perm,
+            lazy: another.lazy,
+            shared: another.shared,
+            file: another.file.clone(),
         }
     }
 
-    fn map(&mut self, page_table: &mut PageTable) {
-        for vpn in self.vpn_range {
-            self.map_one(page_table, vpn);
+    /// Split the area at `vpn`, return the part after it.
+    fn split_off(&mut self, vpn: VirtPageNum) -> Self {
+        let end = self.vpn_range.get_end();
+        let file = self
+            .file_page(vpn)
+            .map(|(inode, offset)| MappedFile { inode, offset });
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), vpn);
+        Self {
+            vpn_range: VPNRange::new(vpn, end),
+            data_frames: self.data_frames.split_off(&vpn),
+            map_type: self.map_type,
+            map_perm: self.map_perm,
+            lazy: self.lazy,
+            shared: self.shared,
+            file,
+        }
+    }
+
+    /// Return the mapped file and the offset in it of the page at `vpn`.
+    fn file_page(&self, vpn: VirtPageNum) -> Option<(Arc<Inode>, usize)> {
+        self.file.as_ref().map(|file| {
+            let offset = file.offset + (vpn.0 - self.vpn_range.get_start().0) * PAGE_SIZE;
+            (file.inode.clone(), offset)
+        })
+    }
+
+    /// Return false if frames run out, leaving the pages mapped so far for `unmap`.
+    fn map(&mut self, page_table: &mut PageTable) -> bool {
+        if self.lazy {
+            return true;
         }
+        self.vpn_range
+            .into_iter()
+            .all(|vpn| self.map_one(page_table, vpn))
     }
 
     fn unmap(&mut self, page_table: &mut PageTable) {
         for vpn in self.vpn_range {
+            // pages of a lazy area may have not been touched
+            if self.lazy && !self.data_frames.contains_key(&vpn) {
+                continue;
+            }
             self.unmap_one(page_table, vpn);
         }
     }
 
-    /// assume that all frames were cleared before
-    fn copy_data(&mut self, page_table: &PageTable, data: &[u8], mut offset: usize) {
+    /// assume that all frames were cleared before,
+    /// return false if there is no frame to swap a page in
+    fn copy_data(&mut self, data: &[u8], mut offset: usize) -> bool {
         assert_eq!(self.map_type, MapType::Framed);
         let mut start = 0;
         let mut current_vpn = self.vpn_range.get_start();
         let len = data.len();
         loop {
             let src = &data[start..len.min(start + PAGE_SIZE - offset)];
-            let dst = &mut page_table
-                .translate(current_vpn)
-                .unwrap()
-                .ppn()
-                .get_bytes_array()[offset..src.len() + offset];
+            // the page may have been swapped out while mapping the others
+            let ppn = match self.data_frames[&current_vpn].ppn() {
+                Some(ppn) => ppn,
+                None => return false,
+            };
+            let dst = &mut ppn.get_bytes_array()[offset..src.len() + offset];
             dst.copy_from_slice(src);
             start += PAGE_SIZE - offset;
             if start >= len {
@@ -102,30 +189,183 @@ impl MapArea {
             current_vpn.step();
             offset = 0;
         }
+        true
     }
 
-    fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
-        let ppn;
+    /// Return false if there is no frame for the page.
+    fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
         match self.map_type {
-            MapType::Identical => ppn = PhysPageNum(vpn.0),
+            MapType::Identical => page_table.map(vpn, PhysPageNum(vpn.0), pte_flags),
             MapType::Framed => {
-                let frame = frame_alloc().unwrap();
-                ppn = frame.ppn;
-                self.data_frames.insert(vpn, frame);
+                let frame = match frame_alloc() {
+                    Some(frame) => frame,
+                    None => return false,
+                };
+                // bytes beyond the end of the file are left zero
+                if let Some((inode, offset)) = self.file_page(vpn) {
+                    inode.read_at(offset, frame.ppn.get_bytes_array());
+                }
+                // map it before the page can be swapped out
+                page_table.map(vpn, frame.ppn, pte_flags);
+                let swappable = self.map_perm.contains(MapPermission::U);
+                let page = Page::new(frame, vpn, page_table.token(), swappable);
+                self.data_frames.insert(vpn, page);
             }
-        };
-        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
-        page_table.map(vpn, ppn, pte_flags);
+        }
+        true
     }
 
     fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
         match self.map_type {
             MapType::Identical => {}
             MapType::Framed => {
-                self.data_frames.remove(&vpn);
+                if let Some(page) = self.data_frames.remove(&vpn) {
+                    if self.shared {
+                        self.write_back(vpn, &page);
+                    }
+                    page.remove_mapping(page_table.token());
+                }
+            }
+        }
+        // a page swapped out is not mapped
+        if page_table.translate(vpn).is_some() {
+            page_table.unmap(vpn);
+        }
+    }
+
+    /// Write the page back to the mapped file if it is dirty, without growing the file.
+    fn write_back(&self, vpn: VirtPageNum, page: &Arc<Page>) {
+        if let Some((inode, offset)) = self.file_page(vpn) {
+            if !page.take_dirty() {
+                return;
+            }
+            let len = inode.size().saturating_sub(offset).min(PAGE_SIZE);
+            // read it without swapping in, which needs a frame
+            let mut data = vec![0u8; PAGE_SIZE];
+            page.read(&mut data);
+            inode.write_at(offset, &data[..len]);
+        }
+    }
+
+    /// Write back pages in the range if the area is a shared mapping of a file.
+    fn sync(&self, range: VPNRange) {
+        if !self.shared {
+            return;
+        }
+        for (&vpn, page) in self.data_frames.range(range.get_start()..range.get_end()) {
+            self.write_back(vpn, page);
+        }
+    }
+
+    /// Map a page which is swapped out, or shared but not mapped in this space,
+    /// a shared page is mapped read-only for copy-on-write.
+    /// Return false if there is no frame to swap it in.
+    fn map_page(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        let page = &self.data_frames[&vpn];
+        let mut map_perm = self.map_perm;
+        if page.shared() && !self.shared {
+            map_perm.remove(MapPermission::W);
+        }
+        page.map(page_table, PTEFlags::from_bits(map_perm.bits).unwrap())
+    }
+
+    /// Share frames with `another`, writable pages become copy-on-write in both spaces
+    /// unless the area is shared.
+    fn share_from(
+        &mut self,
+        page_table: &mut PageTable,
+        another: &MapArea,
+        another_page_table: &mut PageTable,
+    ) {
+        assert_eq!(self.map_type, MapType::Framed);
+        let mut map_perm = self.map_perm;
+        if !self.shared {
+            map_perm.remove(MapPermission::W);
+        }
+        let pte_flags = PTEFlags::from_bits(map_perm.bits).unwrap();
+        for (&vpn, page) in another.data_frames.iter() {
+            // a shared page is never swapped out
+            self.data_frames.insert(vpn, page.clone());
+            page.add_mapping(page_table.token());
+            // while a page swapped out is mapped in either space on the next fault
+            if let Some(pte) = another_page_table.translate(vpn) {
+                page_table.map(vpn, pte.ppn(), pte_flags);
+                another_page_table.remap(vpn, pte.ppn(), pte_flags);
+            }
+        }
+    }
+
+    /// Give the page its own frame if it is shared, then restore write permission.
+    /// Return false if there is no frame for it.
+    fn handle_cow(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+        let ppn = page_table.translate(vpn).unwrap().ppn();
+        let page = self.data_frames.get_mut(&vpn).unwrap();
+        if page.shared() {
+            let frame = match frame_alloc() {
+                Some(frame) => frame,
+                None => return false,
+            };
+            frame
+                .ppn
+                .get_bytes_array()
+                .copy_from_slice(ppn.get_bytes_array());
+            page.remove_mapping(page_table.token());
+            page_table.remap(vpn, frame.ppn, pte_flags);
+            *page = Page::new(frame, vpn, page_table.token(), true);
+        } else {
+            page_table.remap(vpn, ppn, pte_flags);
+        }
+        true
+    }
+
+    /// Change the permission of the area, pages shared by copy-on-write stay read-only.
+    fn set_permission(&mut self, page_table: &mut PageTable, map_perm: MapPermission) {
+        self.map_perm = map_perm;
+        for (&vpn, page) in self.data_frames.iter() {
+            // a page swapped out takes the permission when it is mapped again
+            if let Some(pte) = page_table.translate(vpn) {
+                let mut map_perm = map_perm;
+                if page.shared() && !self.shared {
+                    map_perm.remove(MapPermission::W);
+                }
+                page_table.remap(vpn, pte.ppn(), PTEFlags::from_bits(map_perm.bits).unwrap());
+            }
+        }
+    }
+
+    /// Whether `another` can be appended to this area.
+    fn mergeable(&self, another: &MapArea) -> bool {
+        let file_continued = match (self.file_page(another.vpn_range.get_start()), &another.file) {
+            (Some((inode, offset)), Some(file)) => {
+                inode.inode_id() == file.inode.inode_id() && offset == file.offset
+            }
+            (None, None) => true,
+            _ => false,
+        };
+        self.map_type == another.map_type
+            && self.map_perm == another.map_perm
+            && self.lazy == another.lazy
+            && self.shared == another.shared
+            && file_continued
+    }
+
+    /// Append the area right after this one.
+    fn append(&mut self, mut another: MapArea) {
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), another.vpn_range.get_end());
+        self.data_frames.append(&mut another.data_frames);
+    }
+
+    /// Move the end of the area to `end`, unmapping pages beyond it.
+    fn set_end(&mut self, page_table: &mut PageTable, end: VirtPageNum) {
+        let start = self.vpn_range.get_start();
+        if end < self.vpn_range.get_end() {
+            for vpn in VPNRange::new(end, self.vpn_range.get_end()) {
+                self.unmap_one(page_table, vpn);
             }
         }
-        page_table.unmap(vpn);
+        self.vpn_range = VPNRange::new(start, end);
     }
 
     fn overlap(&self, area: &MapArea) -> bool {
@@ -136,6 +376,9 @@ impl MapArea {
 pub struct MemorySet {
     pub page_table: PageTable,
     areas: Vec<MapArea>,
+    /// The heap is an area from the end of elf segments up to the program break.
+    heap_bottom: usize,
+    brk: usize,
 }
 
 impl MemorySet {
@@ -143,6 +386,8 @@ impl MemorySet {
         Self {
             page_table: PageTable::new(),
             areas: Vec::new(),
+            heap_bottom: 0,
+            brk: 0,
         }
     }
 
@@ -154,9 +399,15 @@ impl MemorySet {
         if self.areas.iter().map(|a| a.overlap(&map_area)).any(|p| p) {
             return Err("map areas overlap");
         }
-        map_area.map(&mut self.page_table);
+        if !map_area.map(&mut self.page_table) {
+            map_area.unmap(&mut self.page_table);
+            return Err("no frame to map the area");
+        }
         if let Some((data, offset)) = data {
-            map_area.copy_data(&self.page_table, data, offset);
+            if !map_area.copy_data(data, offset) {
+                map_area.unmap(&mut self.page_table);
+                return Err("no frame to copy data to");
+            }
         }
         self.areas.push(map_area);
         Ok(())
@@ -174,21 +425,193 @@ impl MemorySet {
         )
     }
 
-    pub fn delete_framed_area(
+    /// Frames of the area are allocated on first touch.
+    pub fn insert_lazy_area(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+    ) -> Result<(), &'static str> {
+        let mut map_area = MapArea::new(start_va, end_va, MapType::Framed, permission);
+        map_area.lazy = true;
+        self.push(map_area, None)
+    }
+
+    /// Frames of the area are allocated at once, so that children share all of them.
+    pub fn insert_shared_area(
         &mut self,
         start_va: VirtAddr,
         end_va: VirtAddr,
+        permission: MapPermission,
     ) -> Result<(), &'static str> {
+        let mut map_area = MapArea::new(start_va, end_va, MapType::Framed, permission);
+        map_area.shared = true;
+        self.push(map_area, None)
+    }
+
+    /// Map `inode` from `offset`, pages of a private mapping are read on first touch,
+    /// while those of a shared one are read at once, so that children share all of them.
+    /// Frames are not shared with other mappings of the file though, which see the writes
+    /// only after they are written back, and only if they are mapped or read afterwards.
+    pub fn insert_file_area(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+        inode: Arc<Inode>,
+        offset: usize,
+        shared: bool,
+    ) -> Result<(), &'static str> {
+        let mut map_area = MapArea::new(start_va, end_va, MapType::Framed, permission);
+        map_area.lazy = !shared;
+        map_area.shared = shared;
+        map_area.file = Some(MappedFile { inode, offset });
+        self.push(map_area, None)
+    }
+
+    /// Split areas across the bounds of `range`, so that each area is either in or out of it.
+    fn split_areas(&mut self, range: VPNRange) {
+        for &bound in [range.get_start(), range.get_end()].iter() {
+            if let Some(area) = self
+                .areas
+                .iter_mut()
+                .find(|area| area.vpn_range.get_start() < bound && bound < area.vpn_range.get_end())
+            {
+                let rest = area.split_off(bound);
+                self.areas.push(rest);
+            }
+        }
+    }
+
+    /// Merge areas next to each other which differ in nothing but ranges,
+    /// while the heap is kept apart for brk.
+    fn merge_areas(&mut self) {
+        let heap_bottom = VirtAddr::from(self.heap_bottom).floor();
+        let heap_end = VirtAddr::from(self.brk).ceil();
+        self.areas.sort_by_key(|area| area.vpn_range.get_start());
+        let mut i = 1;
+        while i < self.areas.len() {
+            let (prev, next) = (&self.areas[i - 1], &self.areas[i]);
+            if prev.vpn_range.get_end() == next.vpn_range.get_start()
+                && next.vpn_range.get_start() != heap_bottom
+                && next.vpn_range.get_start() != heap_end
+                && prev.mergeable(next)
+            {
+                let next = self.areas.remove(i);
+                self.areas[i - 1].append(next);
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    /// Unmap pages in the range, areas partly in it are split.
+    pub fn remove_range(&mut self, start_va: VirtAddr, end_va: VirtAddr) {
         let range = VPNRange::new(start_va.floor(), end_va.ceil());
-        if let Some(index) = self.areas.iter().position(|a| a.vpn_range == range) {
-            let mut area = self.areas.remove(index);
-            area.unmap(&mut self.page_table);
-            Ok(())
-        } else {
-            Err("no such a map area")
+        self.split_areas(range);
+        let mut i = 0;
+        while i < self.areas.len() {
+            if self.areas[i].vpn_range.overlap(&range) {
+                let mut area = self.areas.remove(i);
+                area.unmap(&mut self.page_table);
+            } else {
+                i += 1;
+            }
         }
     }
 
+    /// Write back pages in the range which are shared mappings of files,
+    /// return false if any page in it is not mapped.
+    pub fn sync_range(&self, start_va: VirtAddr, end_va: VirtAddr) -> bool {
+        let range = VPNRange::new(start_va.floor(), end_va.ceil());
+        if !self.is_mapped(range) {
+            return false;
+        }
+        for area in self.areas.iter() {
+            let start = area.vpn_range.get_start().max(range.get_start());
+            let end = area.vpn_range.get_end().min(range.get_end());
+            if start < end {
+                area.sync(VPNRange::new(start, end));
+            }
+        }
+        // the dirty bits cleared may be cached, so that later writes would not set them
+        unsafe {
+            llvm_asm!("sfence.vma" :::: "volatile");
+        }
+        true
+    }
+
+    /// Whether every page in the range is in some area.
+    fn is_mapped(&self, range: VPNRange) -> bool {
+        let mapped: usize = self
+            .areas
+            .iter()
+            .filter(|area| area.vpn_range.overlap(&range))
+            .map(|area| {
+                let start = area.vpn_range.get_start().max(range.get_start());
+                let end = area.vpn_range.get_end().min(range.get_end());
+                end.0 - start.0
+            })
+            .sum();
+        mapped == range.get_end().0 - range.get_start().0
+    }
+
+    /// Change the permission of pages in the range, areas partly in it are split,
+    /// return false if any page in it is not mapped.
+    pub fn protect_range(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+    ) -> bool {
+        let range = VPNRange::new(start_va.floor(), end_va.ceil());
+        if !self.is_mapped(range) {
+            return false;
+        }
+        self.split_areas(range);
+        for area in self.areas.iter_mut() {
+            if area.vpn_range.overlap(&range) {
+                area.set_permission(&mut self.page_table, permission);
+            }
+        }
+        self.merge_areas();
+        // the space is the current one, whose stale entries may be cached
+        unsafe {
+            llvm_asm!("sfence.vma" :::: "volatile");
+        }
+        true
+    }
+
+    /// Whether the range is in user space and does not overlap any area.
+    pub fn is_free(&self, start_va: VirtAddr, end_va: VirtAddr) -> bool {
+        let range = VPNRange::new(start_va.floor(), end_va.ceil());
+        range.get_start().0 > 0
+            && end_va.0 <= USER_STACK_TOP
+            && !self.areas.iter().any(|area| area.vpn_range.overlap(&range))
+    }
+
+    /// Find a free range of `len` bytes from the bottom of the stack limit down,
+    /// return its start.
+    pub fn find_free_range(&self, len: usize) -> Option<VirtAddr> {
+        let pages = VirtAddr::from(len).ceil().0;
+        let mut end = VirtAddr::from(USER_STACK_TOP - USER_STACK_LIMIT).floor();
+        // the first page is left unmapped to catch null pointers
+        while end.0 > pages {
+            let range = VPNRange::new(VirtPageNum(end.0 - pages), end);
+            match self
+                .areas
+                .iter()
+                .filter(|area| area.vpn_range.overlap(&range))
+                .map(|area| area.vpn_range.get_start())
+                .min()
+            {
+                Some(start) => end = start,
+                None => return Some(range.get_start().into()),
+            }
+        }
+        None
+    }
+
     pub fn remove_area_with_start_vpn(&mut self, start_vpn: VirtPageNum) -> Result<(), &'static str> {
         if let Some(index) = self
             .areas
@@ -285,32 +708,71 @@ impl MemorySet {
                 None,
             )
             .unwrap();
+        info!("mapping memory-mapped registers");
+        for &(start, len) in MMIO {
+            memory_set
+                .push(
+                    MapArea::new(
+                        start.into(),
+                        (start + len).into(),
+                        MapType::Identical,
+                        MapPermission::R | MapPermission::W,
+                    ),
+                    None,
+                )
+                .unwrap();
+        }
         memory_set
     }
 
     /// Include sections in elf and trampoline and TrapContext and user stack,
-    /// also returns user_sp and entry point.
-    pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
+    /// also returns user_sp, entry point and the auxiliary vector describing the elf.
+    /// Return None if `elf_data` is not a valid executable.
+    pub fn from_elf(elf_data: &[u8]) -> Option<(Self, usize, usize, Auxv)> {
+        let elf = xmas_elf::ElfFile::new(elf_data).ok()?;
+        let elf_header = elf.header;
+        // program headers are read in place, so they should be 64-bit, aligned and in the file
+        let ph_count = elf_header.pt2.ph_count();
+        let ph_offset = elf_header.pt2.ph_offset() as usize;
+        let ph_size = elf_header.pt2.ph_entry_size() as usize * ph_count as usize;
+        if elf_header.pt1.class() != Class::SixtyFour
+            || elf_header.pt2.ph_entry_size() as usize != mem::size_of::<ProgramHeader64>()
+            || (elf_data.as_ptr() as usize + ph_offset) % mem::align_of::<ProgramHeader64>() != 0
+            || ph_offset.checked_add(ph_size)? > elf_data.len()
+            || ph_size > PAGE_SIZE
+        {
+            return None;
+        }
         let mut memory_set = MemorySet::new_bare();
         // map trampoline
         memory_set.map_trampoline();
         // map program headers of elf, with U flag
-        let elf = xmas_elf::ElfFile::new(elf_data).unwrap();
-        let elf_header = elf.header;
-        let magic = elf_header.pt1.magic;
-        assert_eq!(magic, xmas_elf::header::MAGIC, "invalid elf!");
-        let ph_count = elf_header.pt2.ph_count();
         let mut max_end_vpn = VirtPageNum(0);
         for i in 0..ph_count {
-            let ph = elf.program_header(i).unwrap();
-            if ph.get_type().unwrap() == xmas_elf::program::Type::Load {
+            let ph = elf.program_header(i).ok()?;
+            if ph.get_type().ok()? == xmas_elf::program::Type::Load {
                 debug!(
                     "virtual_addr:{:#x}, mem_size:{:#x}",
                     ph.virtual_addr(),
                     ph.mem_size()
                 );
+                let offset = ph.offset() as usize;
+                let file_size = ph.file_size() as usize;
+                let mem_size = ph.mem_size() as usize;
+                let end = (ph.virtual_addr() as usize).checked_add(mem_size)?;
+                // segments should be backed by the file and stay below the stack
+                if file_size > mem_size
+                    || offset.checked_add(file_size)? > elf_data.len()
+                    || end > USER_STACK_TOP - USER_STACK_LIMIT
+                {
+                    return None;
+                }
+                // an empty segment has no page to load
+                if mem_size == 0 {
+                    continue;
+                }
                 let start_va = (ph.virtual_addr() as usize).into();
-                let end_va = ((ph.virtual_addr() + ph.mem_size()) as usize).into();
+                let end_va = end.into();
                 let mut map_perm = MapPermission::U;
                 let ph_flags = ph.flags();
                 if ph_flags.is_read() {
@@ -323,25 +785,34 @@ impl MemorySet {
                     map_perm.insert(MapPermission::X);
                 }
                 let map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
-                max_end_vpn = map_area.vpn_range.get_end();
+                max_end_vpn = max_end_vpn.max(map_area.vpn_range.get_end());
                 memory_set
                     .push(
                         map_area,
                         Some((
-                            &elf_data
-                                [ph.offset() as usize..(ph.offset() + ph.file_size()) as usize],
+                            &elf_data[offset..offset + file_size],
                             start_va.page_offset(),
                         )),
                     )
-                    .unwrap();
+                    .ok()?;
             }
         }
-        // map user stack with U flag
-        let max_end_va: VirtAddr = max_end_vpn.into();
-        let user_stack_bottom = max_end_va.0 + PAGE_SIZE;
-        let user_stack_top = user_stack_bottom + USER_STACK_SIZE;
+        // map an empty heap with U flag, which grows by brk
+        let heap_bottom: VirtAddr = max_end_vpn.into();
         memory_set
-            .insert_framed_area(
+            .insert_lazy_area(
+                heap_bottom,
+                heap_bottom,
+                MapPermission::R | MapPermission::W | MapPermission::U,
+            )
+            .unwrap();
+        memory_set.heap_bottom = heap_bottom.into();
+        memory_set.brk = heap_bottom.into();
+        // map user stack with U flag, which grows on page faults
+        let user_stack_top = USER_STACK_TOP;
+        let user_stack_bottom = user_stack_top - USER_STACK_SIZE;
+        memory_set
+            .insert_lazy_area(
                 user_stack_bottom.into(),
                 user_stack_top.into(),
                 MapPermission::R | MapPermission::W | MapPermission::U,
@@ -355,20 +826,35 @@ impl MemorySet {
                 MapPermission::R | MapPermission::W,
             )
             .unwrap();
-        (
-            memory_set,
-            user_stack_top,
-            elf_header.pt2.entry_point() as usize,
-        )
+        // program headers are not loaded, so copy them to the top of user stack
+        let phdr = user_stack_top - ph_size;
+        memory_set.copy_to_user(phdr, &elf_data[ph_offset..ph_offset + ph_size]);
+        let entry_point = elf_header.pt2.entry_point() as usize;
+        let auxv = vec![
+            (AT_PHDR, phdr),
+            (AT_PHENT, elf_header.pt2.ph_entry_size() as usize),
+            (AT_PHNUM, ph_count as usize),
+            (AT_PAGESZ, PAGE_SIZE),
+            (AT_ENTRY, entry_point),
+        ];
+        Some((memory_set, phdr, entry_point, auxv))
     }
 
-    pub fn from_existed_user(user_space: &Self) -> Self {
+    /// User pages are shared, writable ones by copy-on-write, while others are copied at once.
+    pub fn from_existed_user(user_space: &mut Self) -> Self {
         let mut memory_set = MemorySet::new_bare();
+        memory_set.heap_bottom = user_space.heap_bottom;
+        memory_set.brk = user_space.brk;
         // map trampoline
         memory_set.map_trampoline();
-        // copy data sections/trap_context/user_stack
+        // share or copy data sections/trap_context/user_stack/heap
         for area in &user_space.areas {
-            let new_area = MapArea::from_another(area);
+            let mut new_area = MapArea::from_another(area);
+            if area.map_perm.contains(MapPermission::U) {
+                new_area.share_from(&mut memory_set.page_table, area, &mut user_space.page_table);
+                memory_set.areas.push(new_area);
+                continue;
+            }
             memory_set.push(new_area, None).unwrap();
             // copy data from another space
             for vpn in area.vpn_range {
@@ -382,10 +868,136 @@ impl MemorySet {
         memory_set
     }
 
+    /// Resolve a page fault caused by `access` to `vpn`, by allocating a lazy page,
+    /// swapping in a page, copying a copy-on-write page or growing the user stack.
+    /// Return false if it is a segmentation fault or there is no frame for the page.
+    pub fn handle_page_fault(&mut self, vpn: VirtPageNum, access: AccessType) -> bool {
+        if !self.areas.iter().any(|area| area.vpn_range.contains(vpn)) && !self.grow_stack(vpn) {
+            return false;
+        }
+        let page_table = &mut self.page_table;
+        let area = self
+            .areas
+            .iter_mut()
+            .find(|area| area.vpn_range.contains(vpn))
+            .unwrap();
+        let permission = match access {
+            AccessType::Read => MapPermission::U | MapPermission::R,
+            AccessType::Write => MapPermission::U | MapPermission::W,
+            AccessType::Execute => MapPermission::U | MapPermission::X,
+        };
+        if !area.map_perm.contains(permission) {
+            return false;
+        }
+        match page_table.translate(vpn) {
+            None if area.data_frames.contains_key(&vpn) => {
+                if !area.map_page(page_table, vpn) {
+                    return false;
+                }
+                // a write to a copy-on-write page swapped in goes to a frame of its own,
+                // as the kernel writes without faulting again
+                let writable = page_table.translate(vpn).unwrap().writable();
+                access != AccessType::Write || writable || area.handle_cow(page_table, vpn)
+            }
+            None if area.lazy => area.map_one(page_table, vpn),
+            None => false,
+            Some(pte) if access == AccessType::Write && !pte.writable() => {
+                area.handle_cow(page_table, vpn)
+            }
+            // resolved already
+            Some(_) => true,
+        }
+    }
+
+    /// Extend the user stack down to `vpn` within `USER_STACK_LIMIT`, return false if
+    /// `vpn` is out of the limit or the stack would overlap other areas.
+    fn grow_stack(&mut self, vpn: VirtPageNum) -> bool {
+        let top = VirtAddr::from(USER_STACK_TOP).floor();
+        if vpn < VirtAddr::from(USER_STACK_TOP - USER_STACK_LIMIT).floor() {
+            return false;
+        }
+        let bottom = match self
+            .areas
+            .iter()
+            .find(|area| area.vpn_range.get_end() == top)
+        {
+            Some(stack) if vpn < stack.vpn_range.get_start() => stack.vpn_range.get_start(),
+            _ => return false,
+        };
+        let range = VPNRange::new(vpn, bottom);
+        if self.areas.iter().any(|area| area.vpn_range.overlap(&range)) {
+            return false;
+        }
+        let stack = self
+            .areas
+            .iter_mut()
+            .find(|area| area.vpn_range.get_end() == top)
+            .unwrap();
+        stack.vpn_range = VPNRange::new(vpn, top);
+        true
+    }
+
+    /// Move the program break to `brk` and return the new one, which is unchanged if `brk`
+    /// is below the bottom of the heap or the heap would overlap other areas.
+    pub fn set_brk(&mut self, brk: usize) -> usize {
+        if brk < self.heap_bottom {
+            return self.brk;
+        }
+        let bottom = VirtAddr::from(self.heap_bottom).floor();
+        let range = VPNRange::new(bottom, VirtAddr::from(brk).ceil());
+        // an mmap area may start at the bottom of an empty heap, which is after the heap,
+        // while the heap may be gone after its bottom is unmapped
+        let index = match self
+            .areas
+            .iter()
+            .position(|area| area.vpn_range.get_start() == bottom)
+        {
+            Some(index) => index,
+            None => return self.brk,
+        };
+        if self
+            .areas
+            .iter()
+            .enumerate()
+            .any(|(i, area)| i != index && area.vpn_range.overlap(&range))
+        {
+            return self.brk;
+        }
+        self.areas[index].set_end(&mut self.page_table, range.get_end());
+        self.brk = brk;
+        brk
+    }
+
+    /// Copy `data` to `va` in this space, allocating lazy pages on the way.
+    pub fn copy_to_user(&mut self, va: usize, data: &[u8]) {
+        let mut start = 0;
+        while start < data.len() {
+            let va = VirtAddr::from(va + start);
+            if self.translate(va.floor()).is_none() {
+                assert!(self.handle_page_fault(va.floor(), AccessType::Write));
+            }
+            let ppn = self.translate(va.floor()).unwrap().ppn();
+            let offset = va.page_offset();
+            let len = (PAGE_SIZE - offset).min(data.len() - start);
+            ppn.get_bytes_array()[offset..offset + len].copy_from_slice(&data[start..start + len]);
+            start += len;
+        }
+    }
+
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
         self.page_table.translate(vpn)
     }
 
+    /// Return the page at `vpn`, which is not swapped out while it is held.
+    pub fn page(&self, vpn: VirtPageNum) -> Option<Arc<Page>> {
+        self.areas
+            .iter()
+            .find(|area| area.vpn_range.contains(vpn))?
+            .data_frames
+            .get(&vpn)
+            .cloned()
+    }
+
     pub fn activate(&self) {
         let satp = self.page_table.token();
         satp::write(satp);
@@ -395,10 +1007,20 @@ impl MemorySet {
     }
 
     pub fn recycle_data_pages(&mut self) {
+        // pages shared with other spaces should forget this one
+        for area in self.areas.iter_mut() {
+            area.unmap(&mut self.page_table);
+        }
         self.areas.clear();
     }
 }
 
+impl Drop for MemorySet {
+    fn drop(&mut self) {
+        self.recycle_data_pages();
+    }
+}
+
 lazy_static! {
     pub static ref KERNEL_SPACE: Mutex<MemorySet> = Mutex::new(MemorySet::new_kernel());
 }
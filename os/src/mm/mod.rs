@@ -3,16 +3,20 @@ mod frame_allocator;
 mod heap_allocator;
 mod memory_set;
 mod page_table;
+mod swap;
 
-pub use address::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
-pub use memory_set::{MapPermission, MemorySet, KERNEL_SPACE};
+pub use address::{PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
+pub use frame_allocator::{frame_alloc, FrameTracker};
+pub use memory_set::{AccessType, MapPermission, MemorySet, AT_NULL, AT_RANDOM, KERNEL_SPACE};
 pub use page_table::{
-    translated_byte_buffer, translated_byte_buffer_copy, translated_refmut, translated_str,
-    PageTableEntry, UserBuffer,
+    translated_byte_buffer, translated_byte_buffer_copy, translated_byte_buffer_mut,
+    translated_ref, translated_refmut, translated_str, PageTable, PageTableEntry, UserBuffer,
 };
+pub use swap::Page;
 
 pub fn init() {
     heap_allocator::init_heap();
     frame_allocator::init_frame_allocator();
     KERNEL_SPACE.lock().activate();
+    swap::init();
 }
@@ -1,9 +1,12 @@
 use super::{
     address::{PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum},
     frame_allocator::{frame_alloc, FrameTracker},
+    memory_set::AccessType,
+    swap::Page,
 };
 use crate::config::PAGE_SIZE;
-use alloc::{string::String, vec, vec::Vec};
+use crate::task::{current_handle_page_fault, current_page};
+use alloc::{string::String, sync::Arc, vec, vec::Vec};
 
 bitflags! {
     pub struct PTEFlags: u8 {
@@ -58,6 +61,10 @@ impl PageTableEntry {
     pub fn executable(&self) -> bool {
         self.flags().contains(PTEFlags::X)
     }
+
+    pub fn dirty(&self) -> bool {
+        self.flags().contains(PTEFlags::D)
+    }
 }
 
 pub struct PageTable {
@@ -93,12 +100,44 @@ impl PageTable {
         *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
     }
 
+    /// Map a mapped page to `ppn` with new `flags`.
+    pub fn remap(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
+        let pte = self.find_pte_create(vpn).unwrap();
+        assert!(pte.is_valid(), "vpn {:?} is invalid before remapping", vpn);
+        *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+    }
+
     pub fn unmap(&mut self, vpn: VirtPageNum) {
         let pte = self.find_pte_create(vpn).unwrap();
         assert!(pte.is_valid(), "vpn {:?} is invalid before unmapping", vpn);
         *pte = PageTableEntry::empty();
     }
 
+    /// Set or clear the accessed bit of a mapped page, return whether it was set.
+    pub fn set_accessed(&mut self, vpn: VirtPageNum, accessed: bool) -> bool {
+        self.set_flag(vpn, PTEFlags::A, accessed)
+    }
+
+    /// Set or clear the dirty bit of a mapped page, return whether it was set.
+    pub fn set_dirty(&mut self, vpn: VirtPageNum, dirty: bool) -> bool {
+        self.set_flag(vpn, PTEFlags::D, dirty)
+    }
+
+    fn set_flag(&mut self, vpn: VirtPageNum, flag: PTEFlags, value: bool) -> bool {
+        let pte = self.find_pte_create(vpn).unwrap();
+        assert!(
+            pte.is_valid(),
+            "vpn {:?} is invalid before setting {:?}",
+            vpn,
+            flag
+        );
+        let mut flags = pte.flags();
+        let was_set = flags.contains(flag);
+        flags.set(flag, value);
+        *pte = PageTableEntry::new(pte.ppn(), flags);
+        was_set
+    }
+
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
         debug!("translate {:?}", vpn);
         self.find_pte(vpn).map(|pte| pte.clone())
@@ -151,20 +190,58 @@ impl PageTable {
     }
 }
 
-pub fn translated_byte_buffer(
+/// Translate `vpn` for the kernel to read, resolving the page fault first if it is not
+/// present, which only happens in the space of current task.
+fn translate_present(page_table: &mut PageTable, vpn: VirtPageNum) -> Option<PageTableEntry> {
+    let pte = page_table.translate(vpn).or_else(|| {
+        if current_handle_page_fault(vpn, AccessType::Read) {
+            page_table.translate(vpn)
+        } else {
+            None
+        }
+    })?;
+    // pages the kernel is using are unlikely to be swapped out
+    page_table.set_accessed(vpn, true);
+    Some(pte)
+}
+
+/// Translate `vpn` for the kernel to write, resolving the page fault first if it is not
+/// writable, which only happens in the space of current task.
+fn translate_for_write(page_table: &mut PageTable, vpn: VirtPageNum) -> Option<PhysPageNum> {
+    let ppn = match page_table.translate(vpn) {
+        Some(pte) if pte.writable() => pte.ppn(),
+        _ if current_handle_page_fault(vpn, AccessType::Write) => page_table
+            .translate(vpn)
+            .filter(|pte| pte.writable())?
+            .ppn(),
+        _ => return None,
+    };
+    page_table.set_accessed(vpn, true);
+    // the kernel writes through its own mapping, which leaves the bit alone
+    page_table.set_dirty(vpn, true);
+    Some(ppn)
+}
+
+/// Split the buffer by pages, translating each page with `translate`.
+/// The buffer must be in the space of current task, whose pages it holds.
+fn translated_pages(
     token: usize,
     ptr: *const u8,
     len: usize,
-) -> Option<Vec<&'static mut [u8]>> {
+    translate: impl Fn(&mut PageTable, VirtPageNum) -> Option<PhysPageNum>,
+) -> Option<UserBuffer> {
     debug!("translate_byte_buffer ptr:{:#x}, len:{}", ptr as usize, len);
-    let page_table = PageTable::from_token(token);
+    let mut page_table = PageTable::from_token(token);
     let mut start = ptr as usize;
     let end = start + len;
     let mut v = Vec::new();
+    let mut pages = Vec::new();
     while start < end {
         let start_va = VirtAddr::from(start);
         let mut vpn = start_va.floor();
-        let ppn = page_table.translate(vpn)?.ppn();
+        let ppn = translate(&mut page_table, vpn)?;
+        // hold the page at once, as translating the next one may swap it out
+        pages.extend(current_page(vpn));
         vpn.step();
         let mut end_va: VirtAddr = vpn.into();
         end_va = end_va.min(VirtAddr::from(end));
@@ -176,7 +253,19 @@ pub fn translated_byte_buffer(
         v.push(&mut ppn.get_bytes_array()[start_offset..end_offset]);
         start = end_va.into();
     }
-    Some(v)
+    Some(UserBuffer { buffers: v, pages })
+}
+
+/// The buffer is only for the kernel to read.
+pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Option<UserBuffer> {
+    translated_pages(token, ptr, len, |page_table, vpn| {
+        Some(translate_present(page_table, vpn)?.ppn())
+    })
+}
+
+/// The buffer is for the kernel to write, see `translate_for_write`.
+pub fn translated_byte_buffer_mut(token: usize, ptr: *mut u8, len: usize) -> Option<UserBuffer> {
+    translated_pages(token, ptr, len, translate_for_write)
 }
 
 pub fn translated_byte_buffer_copy(
@@ -187,7 +276,7 @@ pub fn translated_byte_buffer_copy(
 ) -> Option<usize> {
     assert_eq!(len, data.len());
     let mut start = 0;
-    for buf in translated_byte_buffer(token, ptr, len)? {
+    for buf in translated_byte_buffer_mut(token, ptr, len)?.buffers {
         buf.copy_from_slice(&data[start..start + buf.len()]);
         start += buf.len();
     }
@@ -195,11 +284,12 @@ pub fn translated_byte_buffer_copy(
 }
 
 pub fn translated_str(token: usize, ptr: *const u8) -> Option<String> {
-    let page_table = PageTable::from_token(token);
+    let mut page_table = PageTable::from_token(token);
     let mut string = String::new();
     let mut va = VirtAddr::from(ptr as usize);
     loop {
-        let ch = *(page_table.translate_va(va)?.get_mut::<u8>());
+        let pa: PhysAddr = translate_present(&mut page_table, va.floor())?.ppn().into();
+        let ch = *(PhysAddr::from(pa.0 + va.page_offset()).get_mut::<u8>());
         if ch == 0 {
             break;
         } else {
@@ -210,21 +300,27 @@ pub fn translated_str(token: usize, ptr: *const u8) -> Option<String> {
     Some(string)
 }
 
+pub fn translated_ref<T>(token: usize, ptr: *const T) -> Option<&'static T> {
+    let mut page_table = PageTable::from_token(token);
+    let va = VirtAddr::from(ptr as usize);
+    let pa: PhysAddr = translate_present(&mut page_table, va.floor())?.ppn().into();
+    Some(PhysAddr::from(pa.0 + va.page_offset()).get_mut())
+}
+
 pub fn translated_refmut<T>(token: usize, ptr: *mut T) -> Option<&'static mut T> {
-    let page_table = PageTable::from_token(token);
+    let mut page_table = PageTable::from_token(token);
     let va = VirtAddr::from(ptr as usize);
-    Some(page_table.translate_va(va)?.get_mut())
+    let pa: PhysAddr = translate_for_write(&mut page_table, va.floor())?.into();
+    Some(PhysAddr::from(pa.0 + va.page_offset()).get_mut())
 }
 
+/// A buffer in user space, whose pages are kept from being swapped out until it is dropped.
 pub struct UserBuffer {
     pub buffers: Vec<&'static mut [u8]>,
+    pages: Vec<Arc<Page>>,
 }
 
 impl UserBuffer {
-    pub fn new(buffers: Vec<&'static mut [u8]>) -> Self {
-        Self { buffers }
-    }
-
     pub fn len(&self) -> usize {
         self.buffers.iter().map(|b| b.len()).sum()
     }
@@ -236,6 +332,7 @@ impl IntoIterator for UserBuffer {
     fn into_iter(self) -> Self::IntoIter {
         UserBufferIterator {
             buffers: self.buffers,
+            _pages: self.pages,
             current_buffer: 0,
             current_idx: 0,
         }
@@ -244,6 +341,8 @@ impl IntoIterator for UserBuffer {
 
 pub struct UserBufferIterator {
     buffers: Vec<&'static mut [u8]>,
+    /// Held to keep the pages of `buffers`.
+    _pages: Vec<Arc<Page>>,
     current_buffer: usize,
     current_idx: usize,
 }
@@ -0,0 +1,257 @@
+//! Swapping pages of user space out to the swap device when frames run out.
+
+use super::{frame_alloc, page_table::PTEFlags, FrameTracker, PageTable, PhysPageNum, VirtPageNum};
+use crate::config::{PAGE_SIZE, SWAP_SIZE};
+use crate::drivers::SWAP_DEVICE;
+use alloc::{
+    collections::VecDeque,
+    sync::{Arc, Weak},
+    vec,
+    vec::Vec,
+};
+use core::mem;
+use easy_fs::BLOCK_SZ;
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+const BLOCKS_PER_PAGE: usize = PAGE_SIZE / BLOCK_SZ;
+
+/// A slot of the swap device holding a page, which is freed when dropped.
+struct SwapSlot(usize);
+
+impl SwapSlot {
+    fn alloc() -> Option<Self> {
+        SWAP_ALLOCATOR.lock().alloc().map(SwapSlot)
+    }
+
+    fn write(&self, ppn: PhysPageNum) {
+        for (i, block) in ppn.get_bytes_array().chunks(BLOCK_SZ).enumerate() {
+            SWAP_DEVICE.write_block(self.0 * BLOCKS_PER_PAGE + i, block);
+        }
+    }
+
+    fn read(&self, buf: &mut [u8]) {
+        for (i, block) in buf.chunks_mut(BLOCK_SZ).enumerate() {
+            SWAP_DEVICE.read_block(self.0 * BLOCKS_PER_PAGE + i, block);
+        }
+    }
+}
+
+impl Drop for SwapSlot {
+    fn drop(&mut self) {
+        SWAP_ALLOCATOR.lock().dealloc(self.0);
+    }
+}
+
+struct SwapAllocator {
+    current: usize,
+    end: usize,
+    recycled: Vec<usize>,
+}
+
+impl SwapAllocator {
+    fn alloc(&mut self) -> Option<usize> {
+        if let Some(slot) = self.recycled.pop() {
+            Some(slot)
+        } else if self.current == self.end {
+            None
+        } else {
+            self.current += 1;
+            Some(self.current - 1)
+        }
+    }
+
+    fn dealloc(&mut self, slot: usize) {
+        if slot >= self.current || self.recycled.contains(&slot) {
+            panic!("Swap slot {} has not been allocated!", slot);
+        }
+        self.recycled.push(slot);
+    }
+}
+
+enum Content {
+    Frame(FrameTracker),
+    Swapped(SwapSlot),
+}
+
+struct PageInner {
+    content: Content,
+    /// Tokens of page tables in which the page is mapped.
+    tokens: Vec<usize>,
+    /// Whether the page was written before it was swapped out, as the dirty bit
+    /// is gone with its mapping.
+    dirty: bool,
+}
+
+/// A page of a framed area, whose data is in a frame, or on the swap device if
+/// the page is swappable.
+pub struct Page {
+    vpn: VirtPageNum,
+    inner: Mutex<PageInner>,
+}
+
+impl Page {
+    /// The page should have been mapped to `frame` in the page table `token`.
+    pub fn new(frame: FrameTracker, vpn: VirtPageNum, token: usize, swappable: bool) -> Arc<Self> {
+        let page = Arc::new(Self {
+            vpn,
+            inner: Mutex::new(PageInner {
+                content: Content::Frame(frame),
+                tokens: vec![token],
+                dirty: false,
+            }),
+        });
+        if swappable {
+            CLOCK.lock().push_back(Arc::downgrade(&page));
+        }
+        page
+    }
+
+    pub fn add_mapping(&self, token: usize) {
+        self.inner.lock().tokens.push(token);
+    }
+
+    pub fn remove_mapping(&self, token: usize) {
+        self.inner.lock().tokens.retain(|&t| t != token);
+    }
+
+    /// Whether the page is mapped in more than one space.
+    pub fn shared(&self) -> bool {
+        self.inner.lock().tokens.len() > 1
+    }
+
+    /// Return the frame holding the page, reading it back if it is swapped out,
+    /// or None if there is no frame to read it into.
+    pub fn ppn(self: &Arc<Self>) -> Option<PhysPageNum> {
+        let mut inner = self.inner.lock();
+        self.swap_in(&mut inner)
+    }
+
+    /// Return whether the page has been written since the last call, and clear the dirty
+    /// bits in all the spaces it is mapped in.
+    pub fn take_dirty(&self) -> bool {
+        let mut inner = self.inner.lock();
+        let mut dirty = mem::take(&mut inner.dirty);
+        for &token in inner.tokens.iter() {
+            let mut page_table = PageTable::from_token(token);
+            if page_table.translate(self.vpn).is_some() {
+                dirty |= page_table.set_dirty(self.vpn, false);
+            }
+        }
+        dirty
+    }
+
+    /// Copy the page to `buf`, from the swap device if it is swapped out.
+    pub fn read(&self, buf: &mut [u8]) {
+        match &self.inner.lock().content {
+            Content::Frame(frame) => buf.copy_from_slice(frame.ppn.get_bytes_array()),
+            Content::Swapped(slot) => slot.read(buf),
+        }
+    }
+
+    /// Map the page in `page_table` with `flags`, reading it back if it is swapped out,
+    /// return false if there is no frame to read it into.
+    pub fn map(self: &Arc<Self>, page_table: &mut PageTable, flags: PTEFlags) -> bool {
+        // holding the lock keeps the page from being swapped out before it is mapped
+        let mut inner = self.inner.lock();
+        match self.swap_in(&mut inner) {
+            Some(ppn) => {
+                page_table.map(self.vpn, ppn, flags);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn swap_in(self: &Arc<Self>, inner: &mut PageInner) -> Option<PhysPageNum> {
+        if let Content::Swapped(slot) = &inner.content {
+            let frame = frame_alloc()?;
+            slot.read(frame.ppn.get_bytes_array());
+            inner.content = Content::Frame(frame);
+            CLOCK.lock().push_back(Arc::downgrade(self));
+        }
+        match &inner.content {
+            Content::Frame(frame) => Some(frame.ppn),
+            Content::Swapped(_) => unreachable!(),
+        }
+    }
+
+    /// Swap out the page unless it is shared, recently accessed or in use,
+    /// the accessed bit is cleared to give it a second chance.
+    fn try_swap_out(self: &Arc<Self>) -> bool {
+        // the clock holds a weak reference, so the strong ones are the area's and the one
+        // upgraded by `swap_out`, any other is held by someone using the page, such as a
+        // `UserBuffer`
+        if Arc::strong_count(self) > 2 {
+            return false;
+        }
+        let mut inner = match self.inner.try_lock() {
+            Some(inner) if inner.tokens.len() <= 1 => inner,
+            _ => return false,
+        };
+        let mut page_table = inner
+            .tokens
+            .first()
+            .map(|&token| PageTable::from_token(token));
+        let mapped = match &mut page_table {
+            Some(page_table) if page_table.translate(self.vpn).is_some() => {
+                if page_table.set_accessed(self.vpn, false) {
+                    return false;
+                }
+                true
+            }
+            _ => false,
+        };
+        let slot = match SwapSlot::alloc() {
+            Some(slot) => slot,
+            None => return false,
+        };
+        match &inner.content {
+            Content::Frame(frame) => slot.write(frame.ppn),
+            Content::Swapped(_) => unreachable!(),
+        }
+        if mapped {
+            let mut page_table = page_table.unwrap();
+            inner.dirty |= page_table.translate(self.vpn).unwrap().dirty();
+            page_table.unmap(self.vpn);
+        }
+        // the frame is deallocated here
+        inner.content = Content::Swapped(slot);
+        true
+    }
+}
+
+lazy_static! {
+    static ref SWAP_ALLOCATOR: Mutex<SwapAllocator> = Mutex::new(SwapAllocator {
+        current: 0,
+        end: SWAP_SIZE / PAGE_SIZE,
+        recycled: Vec::new(),
+    });
+    /// Swappable pages in frames, in the order the clock hand visits them.
+    static ref CLOCK: Mutex<VecDeque<Weak<Page>>> = Mutex::new(VecDeque::new());
+}
+
+/// Swap out a page which has not been accessed recently, chosen by the clock algorithm,
+/// return false if there is none.
+pub fn swap_out() -> bool {
+    let mut clock = CLOCK.lock();
+    // the first visit of a page clears its accessed bit, so visit each at most twice
+    for _ in 0..clock.len() * 2 {
+        let weak = match clock.pop_front() {
+            Some(weak) => weak,
+            None => break,
+        };
+        // pages dropped are removed from the clock here
+        if let Some(page) = weak.upgrade() {
+            if page.try_swap_out() {
+                return true;
+            }
+            clock.push_back(weak);
+        }
+    }
+    false
+}
+
+pub fn init() {
+    lazy_static::initialize(&SWAP_DEVICE);
+}
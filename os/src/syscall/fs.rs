@@ -1,10 +1,259 @@
-use crate::fs::make_pipe;
-use crate::mm::{translated_byte_buffer, translated_refmut, UserBuffer};
-use crate::task::{current_task, current_user_token};
+use crate::config::MAX_FD_NUM;
+use crate::errno::{EBADF, EFAULT, EINVAL, EMFILE, ENOENT, ENOTDIR, ERANGE};
+use crate::fs::{
+    find_inode, join_path, link_at, make_dir_at, make_pipe, open_file_at, unlink_at,
+    FileDescriptor, OpenFlags, Stat, ROOT_INODE,
+};
+use crate::mm::{
+    translated_byte_buffer, translated_byte_buffer_copy, translated_byte_buffer_mut,
+    translated_refmut, translated_str,
+};
+use crate::task::{current_process, current_user_token};
+use alloc::sync::Arc;
+use core::mem;
+use easy_fs::Inode;
+
+const AT_FDCWD: isize = -100;
+const AT_REMOVEDIR: u32 = 0x200;
+
+/// Return the current working directory of current process.
+pub fn cwd_inode() -> Result<Arc<Inode>, isize> {
+    let cwd = current_process().acquire_inner_lock().cwd.clone();
+    find_inode(&ROOT_INODE, &cwd)
+}
+
+/// Return the directory which `path` is relative to.
+fn dir_inode(dirfd: isize, path: &str) -> Result<Arc<Inode>, isize> {
+    if path.starts_with('/') {
+        return Ok(ROOT_INODE.clone());
+    }
+    if dirfd == AT_FDCWD {
+        return cwd_inode();
+    }
+    let process = current_process();
+    let inner = process.acquire_inner_lock();
+    match inner.get_file(dirfd as usize) {
+        Some(file) => match file.as_os_inode().map(|file| file.inode()) {
+            Some(inode) if inode.is_dir() => Ok(inode),
+            _ => Err(ENOTDIR),
+        },
+        _ => Err(EBADF),
+    }
+}
+
+pub fn sys_openat(dirfd: isize, path: *const u8, flags: u32) -> isize {
+    let token = current_user_token();
+    let path = match translated_str(token, path) {
+        Some(path) => path,
+        None => return -EFAULT,
+    };
+    let flags = OpenFlags::from_bits_truncate(flags);
+    if !flags.is_valid() {
+        return -EINVAL;
+    }
+    let inode = match dir_inode(dirfd, &path).and_then(|dir| open_file_at(&dir, &path, flags)) {
+        Ok(inode) => inode,
+        Err(errno) => return -errno,
+    };
+    let process = current_process();
+    let mut inner = process.acquire_inner_lock();
+    let fd = match inner.alloc_fd() {
+        Some(fd) => fd,
+        None => return -EMFILE,
+    };
+    inner.fd_table[fd] = Some(FileDescriptor::new(
+        inode,
+        flags.contains(OpenFlags::CLOEXEC),
+    ));
+    fd as isize
+}
+
+pub fn sys_mkdirat(dirfd: isize, path: *const u8, _mode: u32) -> isize {
+    let token = current_user_token();
+    let path = match translated_str(token, path) {
+        Some(path) => path,
+        None => return -EFAULT,
+    };
+    match dir_inode(dirfd, &path).and_then(|dir| make_dir_at(&dir, &path)) {
+        Ok(()) => 0,
+        Err(errno) => -errno,
+    }
+}
+
+pub fn sys_unlinkat(dirfd: isize, path: *const u8, flags: u32) -> isize {
+    let token = current_user_token();
+    let path = match translated_str(token, path) {
+        Some(path) => path,
+        None => return -EFAULT,
+    };
+    if flags & !AT_REMOVEDIR != 0 {
+        return -EINVAL;
+    }
+    let rmdir = flags & AT_REMOVEDIR != 0;
+    match dir_inode(dirfd, &path).and_then(|dir| unlink_at(&dir, &path, rmdir)) {
+        Ok(()) => 0,
+        Err(errno) => -errno,
+    }
+}
+
+/// There are no symbolic links, so `flags` makes no difference.
+pub fn sys_linkat(
+    old_dirfd: isize,
+    old_path: *const u8,
+    new_dirfd: isize,
+    new_path: *const u8,
+    _flags: u32,
+) -> isize {
+    let token = current_user_token();
+    let (old_path, new_path) = match (
+        translated_str(token, old_path),
+        translated_str(token, new_path),
+    ) {
+        (Some(old_path), Some(new_path)) => (old_path, new_path),
+        _ => return -EFAULT,
+    };
+    let result = dir_inode(old_dirfd, &old_path).and_then(|old_dir| {
+        let new_dir = dir_inode(new_dirfd, &new_path)?;
+        link_at(&old_dir, &old_path, &new_dir, &new_path)
+    });
+    match result {
+        Ok(()) => 0,
+        Err(errno) => -errno,
+    }
+}
+
+pub fn sys_getdents64(fd: usize, buf: *mut u8, len: usize) -> isize {
+    let token = current_user_token();
+    let process = current_process();
+    let inner = process.acquire_inner_lock();
+    let file = match inner.get_file(fd) {
+        Some(file) => file,
+        None => return -EBADF,
+    };
+    drop(inner);
+    let result = match file.as_os_inode() {
+        Some(file) => {
+            file.read_dirents(len, |v| translated_byte_buffer_copy(token, buf, v.len(), v))
+        }
+        None => Err(ENOTDIR),
+    };
+    match result {
+        Ok(len) => len as isize,
+        Err(errno) => -errno,
+    }
+}
+
+pub fn sys_chdir(path: *const u8) -> isize {
+    let token = current_user_token();
+    let path = match translated_str(token, path) {
+        Some(path) => path,
+        None => return -EFAULT,
+    };
+    if path.is_empty() {
+        return -ENOENT;
+    }
+    match cwd_inode().and_then(|dir| find_inode(&dir, &path)) {
+        Ok(inode) if inode.is_dir() => {
+            let process = current_process();
+            let mut inner = process.acquire_inner_lock();
+            inner.cwd = join_path(&inner.cwd, &path);
+            0
+        }
+        Ok(_) => -ENOTDIR,
+        Err(errno) => -errno,
+    }
+}
+
+/// Return the length of the path, including the ending '\0'.
+pub fn sys_getcwd(buf: *mut u8, size: usize) -> isize {
+    let token = current_user_token();
+    let mut cwd = current_process().acquire_inner_lock().cwd.clone();
+    cwd.push('\0');
+    if cwd.len() > size {
+        return -ERANGE;
+    }
+    match translated_byte_buffer_copy(token, buf, cwd.len(), cwd.as_bytes()) {
+        Some(len) => len as isize,
+        None => -EFAULT,
+    }
+}
+
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    let process = current_process();
+    let inner = process.acquire_inner_lock();
+    let file = match inner.get_file(fd) {
+        Some(file) => file,
+        None => return -EBADF,
+    };
+    drop(inner);
+    match file.seek(offset, whence) {
+        Ok(offset) => offset as isize,
+        Err(errno) => -errno,
+    }
+}
+
+pub fn sys_pread64(fd: usize, buf: *mut u8, len: usize, offset: usize) -> isize {
+    let token = current_user_token();
+    let process = current_process();
+    let inner = process.acquire_inner_lock();
+    let file = match inner.get_file(fd) {
+        Some(file) if file.readable() => file,
+        _ => return -EBADF,
+    };
+    drop(inner);
+    let buf = match translated_byte_buffer_mut(token, buf, len) {
+        Some(buf) => buf,
+        None => return -EFAULT,
+    };
+    match file.read_at(offset, buf) {
+        Ok(len) => len as isize,
+        Err(errno) => -errno,
+    }
+}
+
+pub fn sys_pwrite64(fd: usize, buf: *const u8, len: usize, offset: usize) -> isize {
+    let token = current_user_token();
+    let process = current_process();
+    let inner = process.acquire_inner_lock();
+    let file = match inner.get_file(fd) {
+        Some(file) if file.writable() => file,
+        _ => return -EBADF,
+    };
+    drop(inner);
+    let buf = match translated_byte_buffer(token, buf, len) {
+        Some(buf) => buf,
+        None => return -EFAULT,
+    };
+    match file.write_at(offset, buf) {
+        Ok(len) => len as isize,
+        Err(errno) => -errno,
+    }
+}
+
+pub fn sys_fstat(fd: usize, st: *mut Stat) -> isize {
+    let token = current_user_token();
+    let process = current_process();
+    let inner = process.acquire_inner_lock();
+    let file = match inner.get_file(fd) {
+        Some(file) => file,
+        None => return -EBADF,
+    };
+    drop(inner);
+    let stat = file.stat();
+    match translated_byte_buffer_copy(
+        token,
+        st as *mut u8,
+        mem::size_of::<Stat>(),
+        stat.as_bytes(),
+    ) {
+        Some(_) => 0,
+        None => -EFAULT,
+    }
+}
 
 pub fn sys_close(fd: usize) -> isize {
-    let task = current_task().unwrap();
-    let mut inner = task.acquire_inner_lock();
+    let process = current_process();
+    let mut inner = process.acquire_inner_lock();
     if fd >= inner.fd_table.len() {
         return -1;
     }
@@ -15,30 +264,94 @@ pub fn sys_close(fd: usize) -> isize {
     0
 }
 
+pub fn sys_dup(fd: usize) -> isize {
+    let process = current_process();
+    let mut inner = process.acquire_inner_lock();
+    let file = match inner.get_file(fd) {
+        Some(file) => file,
+        None => return -EBADF,
+    };
+    let new_fd = match inner.alloc_fd() {
+        Some(fd) => fd,
+        None => return -EMFILE,
+    };
+    inner.fd_table[new_fd] = Some(FileDescriptor::new(file, false));
+    new_fd as isize
+}
+
+/// Close `new_fd` first if it is in use, only `O_CLOEXEC` is allowed in `flags`.
+pub fn sys_dup3(old_fd: usize, new_fd: usize, flags: u32) -> isize {
+    let flags = match OpenFlags::from_bits(flags) {
+        Some(flags) if (flags - OpenFlags::CLOEXEC).is_empty() => flags,
+        _ => return -EINVAL,
+    };
+    if old_fd == new_fd {
+        return -EINVAL;
+    }
+    let process = current_process();
+    let mut inner = process.acquire_inner_lock();
+    let file = match inner.get_file(old_fd) {
+        Some(file) => file,
+        None => return -EBADF,
+    };
+    if new_fd >= MAX_FD_NUM {
+        return -EBADF;
+    }
+    if new_fd >= inner.fd_table.len() {
+        inner.fd_table.resize(new_fd + 1, None);
+    }
+    inner.fd_table[new_fd] = Some(FileDescriptor::new(
+        file,
+        flags.contains(OpenFlags::CLOEXEC),
+    ));
+    new_fd as isize
+}
+
 pub fn sys_pipe(pipe: *mut usize) -> isize {
     let token = current_user_token();
-    let task = current_task().unwrap();
-    let mut inner = task.acquire_inner_lock();
+    // translate before locking, as writing to a copy-on-write page acquires the lock
+    let (read_ref, write_ref) = match (
+        translated_refmut(token, pipe),
+        translated_refmut(token, unsafe { pipe.add(1) }),
+    ) {
+        (Some(read_ref), Some(write_ref)) => (read_ref, write_ref),
+        _ => return -EFAULT,
+    };
+    let process = current_process();
+    let mut inner = process.acquire_inner_lock();
     let (pipe_read, pipe_write) = make_pipe();
-    let read_fd = inner.alloc_fd();
-    inner.fd_table[read_fd] = Some(pipe_read);
-    let write_fd = inner.alloc_fd();
-    inner.fd_table[write_fd] = Some(pipe_write);
-    *translated_refmut(token, pipe).unwrap() = read_fd;
-    *translated_refmut(token, unsafe { pipe.add(1) }).unwrap() = write_fd;
+    let read_fd = match inner.alloc_fd() {
+        Some(fd) => fd,
+        None => return -EMFILE,
+    };
+    inner.fd_table[read_fd] = Some(FileDescriptor::new(pipe_read, false));
+    let write_fd = match inner.alloc_fd() {
+        Some(fd) => fd,
+        None => {
+            inner.fd_table[read_fd] = None;
+            return -EMFILE;
+        }
+    };
+    inner.fd_table[write_fd] = Some(FileDescriptor::new(pipe_write, false));
+    drop(inner);
+    *read_ref = read_fd;
+    *write_ref = write_fd;
     0
 }
 
 pub fn sys_read(fd: usize, buf: *mut u8, len: usize) -> isize {
     let token = current_user_token();
-    let task = current_task().unwrap();
-    let inner = task.acquire_inner_lock();
-    if let Some(Some(file)) = inner.fd_table.get(fd) {
-        let file = file.clone();
+    let process = current_process();
+    let inner = process.acquire_inner_lock();
+    if let Some(file) = inner.get_file(fd) {
         // release Task lock manually to avoid deadlock
         drop(inner);
-        if let Some(buffers) = translated_byte_buffer(token, buf, len) {
-            file.read(UserBuffer::new(buffers)) as isize
+        if !file.readable() {
+            warn!("File not readable in sys_read!");
+            return -1;
+        }
+        if let Some(buf) = translated_byte_buffer_mut(token, buf, len) {
+            file.read(buf) as isize
         } else {
             warn!("Illegal memory region in sys_read!");
             -1
@@ -51,14 +364,20 @@ pub fn sys_read(fd: usize, buf: *mut u8, len: usize) -> isize {
 
 pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
     let token = current_user_token();
-    let task = current_task().unwrap();
-    let inner = task.acquire_inner_lock();
-    if let Some(Some(file)) = inner.fd_table.get(fd) {
-        let file = file.clone();
+    let process = current_process();
+    let inner = process.acquire_inner_lock();
+    if let Some(file) = inner.get_file(fd) {
         // release Task lock manually to avoid deadlock
         drop(inner);
-        if let Some(buffers) = translated_byte_buffer(token, buf, len) {
-            file.write(UserBuffer::new(buffers)) as isize
+        if !file.writable() {
+            warn!("File not writable in sys_write!");
+            return -1;
+        }
+        if let Some(buf) = translated_byte_buffer(token, buf, len) {
+            match file.write(buf) {
+                Ok(len) => len as isize,
+                Err(errno) => -errno,
+            }
         } else {
             warn!("Illegal memory region in sys_write!");
             -1
@@ -1,18 +1,32 @@
-use crate::config::PAGE_SIZE;
+use crate::config::{PAGE_SIZE, USER_STACK_TOP};
+use crate::errno::{EACCES, EBADF, EINVAL, ENODEV, ENOMEM};
 use crate::mm::{MapPermission, VirtAddr};
-use crate::task::{current_delete_framed_area, current_insert_framed_area};
+use crate::task::{current_process, current_set_brk};
 
 const PROT_READ: usize = 0x1;
 const PROT_WRITE: usize = 0x2;
 const PROT_EXEC: usize = 0x4;
 const PROT_ALL: usize = PROT_READ | PROT_WRITE | PROT_EXEC;
+const MAP_SHARED: usize = 0x01;
+const MAP_PRIVATE: usize = 0x02;
+const MAP_FIXED: usize = 0x10;
+const MAP_ANONYMOUS: usize = 0x20;
+const MS_ASYNC: usize = 1;
+const MS_INVALIDATE: usize = 2;
+const MS_SYNC: usize = 4;
 
-fn ceil (num: usize, bound: usize) -> usize {
-    (num + bound - 1) / bound * bound
+fn ceil (num: usize, bound: usize) -> Option<usize> {
+    Some(num.checked_add(bound - 1)? / bound * bound)
 }
 
+/// Return the end of `len` bytes from `start`, or None if it goes beyond user space.
+fn user_range_end(start: usize, len: usize) -> Option<usize> {
+    start.checked_add(len).filter(|&end| end <= USER_STACK_TOP)
+}
+
+/// `PROT_NONE` maps pages which cannot be accessed at all.
 fn get_map_permission(prot: usize) -> Option<MapPermission> {
-    if (prot & PROT_ALL == 0) || (prot & !PROT_ALL != 0) {
+    if prot & !PROT_ALL != 0 {
         return None;
     }
     let mut perm = MapPermission::U;
@@ -28,37 +42,177 @@ fn get_map_permission(prot: usize) -> Option<MapPermission> {
     Some(perm)
 }
 
-pub fn mmap(start: usize, len: usize, prot: usize) -> isize {
-    let start_va = VirtAddr::from(start);
-    if !start_va.aligned() {
-        warn!("start address not aligned");
-        return -1;
+/// Return the start address of the new mapping.
+pub fn mmap(
+    start: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> isize {
+    if len == 0 || offset % PAGE_SIZE != 0 {
+        return -EINVAL;
     }
-    if let Some(permission) = get_map_permission(prot) {
-        let end_va = VirtAddr::from(start + len);
-        if let Err(e) = current_insert_framed_area(start_va, end_va, permission) {
-            warn!("{}", e);
-            -1
-        } else {
-            ceil(len, PAGE_SIZE) as isize
+    let permission = match get_map_permission(prot) {
+        Some(permission) => permission,
+        None => {
+            warn!("invalid protection bits");
+            return -EINVAL;
+        }
+    };
+    let shared = match flags & (MAP_SHARED | MAP_PRIVATE) {
+        MAP_SHARED => true,
+        MAP_PRIVATE => false,
+        _ => return -EINVAL,
+    };
+    let len = match ceil(len, PAGE_SIZE) {
+        Some(len) => len,
+        None => return -ENOMEM,
+    };
+    let process = current_process();
+    let mut inner = process.acquire_inner_lock();
+    // pages are mapped from the inode, which stays even if the fd is closed
+    let inode = if flags & MAP_ANONYMOUS != 0 {
+        None
+    } else {
+        let file = match inner.get_file(fd) {
+            Some(file) => file,
+            None => return -EBADF,
+        };
+        let inode = match file.as_os_inode() {
+            Some(os_inode) if !os_inode.inode().is_dir() => os_inode.inode(),
+            _ => return -ENODEV,
+        };
+        // pages of a shared mapping are written back to the file
+        if !file.readable() || (shared && prot & PROT_WRITE != 0 && !file.writable()) {
+            return -EACCES;
         }
+        Some(inode)
+    };
+    let memory_set = &mut inner.memory_set;
+    let start = if flags & MAP_FIXED != 0 {
+        if !VirtAddr::from(start).aligned() {
+            warn!("start address not aligned");
+            return -EINVAL;
+        }
+        let end = match user_range_end(start, len) {
+            Some(end) if start != 0 => end,
+            _ => return -ENOMEM,
+        };
+        // existing mappings in the range are replaced
+        memory_set.remove_range(start.into(), end.into());
+        start
     } else {
-        warn!("invalid protection bits");
-        -1
+        // take the start as a hint, which is used if the range is free
+        let hint = start / PAGE_SIZE * PAGE_SIZE;
+        if hint
+            .checked_add(len)
+            .map_or(false, |end| memory_set.is_free(hint.into(), end.into()))
+        {
+            hint
+        } else {
+            match memory_set.find_free_range(len) {
+                Some(start_va) => start_va.into(),
+                None => return -ENOMEM,
+            }
+        }
+    };
+    let (start_va, end_va) = (start.into(), (start + len).into());
+    let result = match inode {
+        Some(inode) => {
+            memory_set.insert_file_area(start_va, end_va, permission, inode, offset, shared)
+        }
+        None if shared => memory_set.insert_shared_area(start_va, end_va, permission),
+        // frames are allocated on first touch
+        None => memory_set.insert_lazy_area(start_va, end_va, permission),
+    };
+    match result {
+        Ok(()) => start as isize,
+        Err(e) => {
+            warn!("{}", e);
+            -ENOMEM
+        }
     }
 }
 
+/// Unmap pages in the range, which may cover parts of areas or nothing at all.
 pub fn munmap(start: usize, len: usize) -> isize {
-    let start_va = VirtAddr::from(start);
-    if !start_va.aligned() {
+    if !VirtAddr::from(start).aligned() {
         warn!("start address not aligned");
-        return -1;
+        return -EINVAL;
     }
-    let end_va = VirtAddr::from(start + len);
-    if let Err(e) = current_delete_framed_area(start_va, end_va) {
-        warn!("{}", e);
-        -1
+    let len = match ceil(len, PAGE_SIZE) {
+        Some(len) if len != 0 => len,
+        _ => return -EINVAL,
+    };
+    let end = match user_range_end(start, len) {
+        Some(end) => end,
+        None => return -EINVAL,
+    };
+    current_process()
+        .acquire_inner_lock()
+        .memory_set
+        .remove_range(start.into(), end.into());
+    len as isize
+}
+
+/// Change the protection of pages in the range, all of which should be mapped.
+pub fn mprotect(start: usize, len: usize, prot: usize) -> isize {
+    if !VirtAddr::from(start).aligned() {
+        warn!("start address not aligned");
+        return -EINVAL;
+    }
+    let permission = match get_map_permission(prot) {
+        Some(permission) => permission,
+        None => {
+            warn!("invalid protection bits");
+            return -EINVAL;
+        }
+    };
+    let end = match ceil(len, PAGE_SIZE).and_then(|len| user_range_end(start, len)) {
+        Some(end) => end,
+        None => return -ENOMEM,
+    };
+    if current_process()
+        .acquire_inner_lock()
+        .memory_set
+        .protect_range(start.into(), end.into(), permission)
+    {
+        0
     } else {
-        ceil(len, PAGE_SIZE) as isize
+        -ENOMEM
     }
 }
+
+/// Write back pages of shared file mappings in the range, which is done at once
+/// even for `MS_ASYNC`.
+pub fn msync(start: usize, len: usize, flags: usize) -> isize {
+    if !VirtAddr::from(start).aligned() {
+        warn!("start address not aligned");
+        return -EINVAL;
+    }
+    if flags & !(MS_ASYNC | MS_INVALIDATE | MS_SYNC) != 0
+        || flags & (MS_ASYNC | MS_SYNC) == MS_ASYNC | MS_SYNC
+    {
+        return -EINVAL;
+    }
+    let end = match ceil(len, PAGE_SIZE).and_then(|len| user_range_end(start, len)) {
+        Some(end) => end,
+        None => return -ENOMEM,
+    };
+    if current_process()
+        .acquire_inner_lock()
+        .memory_set
+        .sync_range(start.into(), end.into())
+    {
+        0
+    } else {
+        -ENOMEM
+    }
+}
+
+/// Return the new program break, or the current one if it cannot be moved to `brk`.
+pub fn brk(brk: usize) -> isize {
+    current_set_brk(brk) as isize
+}
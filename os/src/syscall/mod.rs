@@ -1,41 +1,126 @@
+const SYSCALL_GETCWD: usize = 17;
+const SYSCALL_DUP: usize = 23;
+const SYSCALL_DUP3: usize = 24;
+const SYSCALL_MKDIRAT: usize = 34;
+const SYSCALL_UNLINKAT: usize = 35;
+const SYSCALL_LINKAT: usize = 37;
+const SYSCALL_CHDIR: usize = 49;
+const SYSCALL_OPENAT: usize = 56;
 const SYSCALL_CLOSE: usize = 57;
 const SYSCALL_PIPE: usize = 59;
+const SYSCALL_GETDENTS64: usize = 61;
+const SYSCALL_LSEEK: usize = 62;
 const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
+const SYSCALL_PREAD64: usize = 67;
+const SYSCALL_PWRITE64: usize = 68;
+const SYSCALL_FSTAT: usize = 80;
 const SYSCALL_EXIT: usize = 93;
+const SYSCALL_NANOSLEEP: usize = 101;
+const SYSCALL_CLOCK_NANOSLEEP: usize = 115;
 const SYSCALL_YIELD: usize = 124;
+const SYSCALL_KILL: usize = 129;
+const SYSCALL_SIGACTION: usize = 134;
+const SYSCALL_SIGPROCMASK: usize = 135;
+const SYSCALL_SIGRETURN: usize = 139;
 const SYSCALL_SET_PRIORITY: usize = 140;
 const SYSCALL_GET_TIME: usize = 169;
 const SYSCALL_GETPID: usize = 172;
+const SYSCALL_GETTID: usize = 178;
+const SYSCALL_BRK: usize = 214;
 const SYSCALL_MUNMAP: usize = 215;
 const SYSCALL_FORK: usize = 220;
-const SYSCALL_EXEC: usize = 221;
+const SYSCALL_EXECVE: usize = 221;
 const SYSCALL_MMAP: usize = 222;
+const SYSCALL_MPROTECT: usize = 226;
+const SYSCALL_MSYNC: usize = 227;
 const SYSCALL_WAITPID: usize = 260;
 const SYSCALL_SPAWN: usize = 400;
+const SYSCALL_THREAD_CREATE: usize = 1000;
+const SYSCALL_WAITTID: usize = 1002;
 
 mod fs;
 mod memory;
 mod process;
+mod signal;
+mod thread;
 mod time;
 
-pub fn syscall(id: usize, args: [usize; 3]) -> isize {
+use crate::fs::Stat;
+use crate::task::SignalAction;
+
+pub fn syscall(id: usize, args: [usize; 6]) -> isize {
     match id {
+        SYSCALL_GETCWD => fs::sys_getcwd(args[0] as *mut u8, args[1]),
+        SYSCALL_DUP => fs::sys_dup(args[0]),
+        SYSCALL_DUP3 => fs::sys_dup3(args[0], args[1], args[2] as u32),
+        SYSCALL_MKDIRAT => fs::sys_mkdirat(args[0] as isize, args[1] as *const u8, args[2] as u32),
+        SYSCALL_UNLINKAT => {
+            fs::sys_unlinkat(args[0] as isize, args[1] as *const u8, args[2] as u32)
+        }
+        SYSCALL_LINKAT => fs::sys_linkat(
+            args[0] as isize,
+            args[1] as *const u8,
+            args[2] as isize,
+            args[3] as *const u8,
+            args[4] as u32,
+        ),
+        SYSCALL_CHDIR => fs::sys_chdir(args[0] as *const u8),
+        SYSCALL_OPENAT => fs::sys_openat(args[0] as isize, args[1] as *const u8, args[2] as u32),
         SYSCALL_CLOSE => fs::sys_close(args[0]),
         SYSCALL_PIPE => fs::sys_pipe(args[0] as *mut usize),
+        SYSCALL_GETDENTS64 => fs::sys_getdents64(args[0], args[1] as *mut u8, args[2]),
+        SYSCALL_LSEEK => fs::sys_lseek(args[0], args[1] as isize, args[2]),
         SYSCALL_READ => fs::sys_read(args[0], args[1] as *mut u8, args[2]),
         SYSCALL_WRITE => fs::sys_write(args[0], args[1] as *const u8, args[2]),
+        SYSCALL_PREAD64 => fs::sys_pread64(args[0], args[1] as *mut u8, args[2], args[3]),
+        SYSCALL_PWRITE64 => fs::sys_pwrite64(args[0], args[1] as *const u8, args[2], args[3]),
+        SYSCALL_FSTAT => fs::sys_fstat(args[0], args[1] as *mut Stat),
         SYSCALL_EXIT => process::sys_exit(args[0] as i32),
+        SYSCALL_NANOSLEEP => time::sys_nanosleep(
+            args[0] as *const time::TimeSpec,
+            args[1] as *mut time::TimeSpec,
+        ),
+        SYSCALL_CLOCK_NANOSLEEP => time::sys_clock_nanosleep(
+            args[0],
+            args[1],
+            args[2] as *const time::TimeSpec,
+            args[3] as *mut time::TimeSpec,
+        ),
         SYSCALL_YIELD => process::sys_yield(),
+        SYSCALL_KILL => signal::sys_kill(args[0] as isize, args[1]),
+        SYSCALL_SIGACTION => signal::sys_sigaction(
+            args[0],
+            args[1] as *const SignalAction,
+            args[2] as *mut SignalAction,
+        ),
+        SYSCALL_SIGPROCMASK => {
+            signal::sys_sigprocmask(args[0], args[1] as *const u32, args[2] as *mut u32)
+        }
+        SYSCALL_SIGRETURN => signal::sys_sigreturn(),
         SYSCALL_SET_PRIORITY => process::sys_set_priority(args[0] as isize),
         SYSCALL_GET_TIME => time::sys_get_time(args[0] as *mut time::TimeVal, args[1]),
         SYSCALL_GETPID => process::sys_getpid(),
+        SYSCALL_GETTID => thread::sys_gettid(),
+        SYSCALL_BRK => memory::brk(args[0]),
         SYSCALL_MUNMAP => memory::munmap(args[0], args[1]),
         SYSCALL_FORK => process::sys_fork(),
-        SYSCALL_EXEC => process::sys_exec(args[0] as *const u8),
-        SYSCALL_MMAP => memory::mmap(args[0], args[1], args[2]),
-        SYSCALL_WAITPID => process::sys_waitpid(args[0] as isize, args[1] as *mut i32),
-        SYSCALL_SPAWN => process::sys_spawn(args[0] as *const u8),
+        SYSCALL_EXECVE => process::sys_execve(
+            args[0] as *const u8,
+            args[1] as *const usize,
+            args[2] as *const usize,
+        ),
+        SYSCALL_MMAP => memory::mmap(args[0], args[1], args[2], args[3], args[4], args[5]),
+        SYSCALL_MPROTECT => memory::mprotect(args[0], args[1], args[2]),
+        SYSCALL_MSYNC => memory::msync(args[0], args[1], args[2]),
+        SYSCALL_WAITPID => process::sys_waitpid(args[0] as isize, args[1] as *mut i32, args[2]),
+        SYSCALL_SPAWN => process::sys_spawn(
+            args[0] as *const u8,
+            args[1] as *const usize,
+            args[2] as *const usize,
+        ),
+        SYSCALL_THREAD_CREATE => thread::sys_thread_create(args[0], args[1]),
+        SYSCALL_WAITTID => thread::sys_waittid(args[0], args[1] as *mut i32),
         _ => panic!("Unsupported syscall id: {}", id),
     }
 }
@@ -1,11 +1,15 @@
-use alloc::sync::Arc;
+use alloc::{string::String, sync::Arc, vec::Vec};
+use core::mem;
 
+use super::fs::cwd_inode;
 use crate::{
-    loader::get_app_data_by_name,
-    mm::{translated_refmut, translated_str},
+    config::ARG_MAX,
+    errno::{EACCES, EINTR, ENOEXEC},
+    fs::{open_file_at, OpenFlags},
+    mm::{translated_ref, translated_refmut, translated_str},
     task::{
-        add_task, current_task, current_user_token, exit_current_and_run_next, set_current_prio,
-        suspend_current_and_run_next,
+        add_task, block_current_and_run_next, current_process, current_task, current_user_token,
+        exit_current_and_run_next, set_current_prio, suspend_current_and_run_next,
     },
 };
 
@@ -24,13 +28,14 @@ pub fn sys_set_priority(prio: isize) -> isize {
 }
 
 pub fn sys_getpid() -> isize {
-    current_task().unwrap().getpid() as isize
+    current_process().getpid() as isize
 }
 
 pub fn sys_fork() -> isize {
-    let current_task = current_task().unwrap();
-    let new_task = current_task.fork();
-    let new_pid = new_task.getpid();
+    let current_process = current_process();
+    let new_process = current_process.fork();
+    let new_pid = new_process.getpid();
+    let new_task = new_process.main_task();
     // modify trap context of new_task, because it returns immediately after switching
     // for child process, fork returns 0
     new_task.acquire_inner_lock().get_trap_ctx().x[10] = 0;
@@ -39,69 +44,150 @@ pub fn sys_fork() -> isize {
     new_pid as isize
 }
 
-pub fn sys_exec(path: *const u8) -> isize {
+/// Read the null-terminated array of strings at `args`, a null `args` means an empty array.
+fn translated_args(token: usize, args: *const usize) -> Option<Vec<String>> {
+    let mut v: Vec<String> = Vec::new();
+    if args.is_null() {
+        return Some(v);
+    }
+    loop {
+        let arg = *translated_ref(token, unsafe { args.add(v.len()) })?;
+        if arg == 0 {
+            break;
+        }
+        v.push(translated_str(token, arg as *const u8)?);
+    }
+    Some(v)
+}
+
+/// Size the strings and their pointers take on the user stack.
+fn args_size(args: &[String]) -> usize {
+    args.iter()
+        .map(|arg| arg.len() + 1 + mem::size_of::<usize>())
+        .sum()
+}
+
+/// Return argc, which is passed to the new program in a0, -EACCES if `path` is a directory,
+/// -ENOEXEC if it is not a valid executable, or -EBUSY if other threads of the process
+/// are still running.
+pub fn sys_execve(path: *const u8, args: *const usize, envs: *const usize) -> isize {
     let token = current_user_token();
-    if let Some(path) = translated_str(token, path) {
-        if let Some(elf_data) = get_app_data_by_name(&path) {
-            let task = current_task().unwrap();
-            task.exec(elf_data);
-            0
+    if let (Some(path), Some(args), Some(envs)) = (
+        translated_str(token, path),
+        translated_args(token, args),
+        translated_args(token, envs),
+    ) {
+        if args_size(&args) + args_size(&envs) > ARG_MAX {
+            warn!("Arguments too long in sys_execve!");
+            return -1;
+        }
+        if let Ok(app_inode) =
+            cwd_inode().and_then(|dir| open_file_at(&dir, &path, OpenFlags::RDONLY))
+        {
+            if app_inode.inode().is_dir() {
+                return -EACCES;
+            }
+            let elf_data = app_inode.read_all();
+            match current_process().exec(elf_data.as_slice(), &args, &envs) {
+                Ok(()) => args.len() as isize,
+                Err(errno) => -errno,
+            }
         } else {
             warn!("No such application name.");
             -1
         }
     } else {
-        warn!("Illegal memory region in sys_exec!");
+        warn!("Illegal memory region in sys_execve!");
         -1
     }
 }
 
+/// Return at once if no child has exited yet.
+const WNOHANG: usize = 1;
+
 /// If there is not a child process whose pid is same as given, return -1.
-/// Else if there is a child process but it is still running, return -2.
-pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
+/// Else if there is a child process but it is still running, block until it exits,
+/// or return 0 with `WNOHANG`, -EINTR if a signal comes first.
+pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32, options: usize) -> isize {
     let task = current_task().unwrap();
-    // find a child process
-
-    // ---- hold current PCB lock
-    let mut inner = task.acquire_inner_lock();
-    let mut target = inner
-        .children
-        .iter()
-        .enumerate()
-        .filter(|(_, t)| pid == -1 || pid as usize == t.getpid())
-        .peekable();
-    if target.peek().is_none() {
-        // specified child process not found
-        return -1;
-    }
-    if let Some((index, _)) = target.find(|(_, t)| t.acquire_inner_lock().is_zombie()) {
-        let child = inner.children.remove(index);
-        // confirm that child will be deallocated after removing from children list
-        assert_eq!(Arc::strong_count(&child), 1);
-        let found_pid = child.getpid();
-        let exit_code = child.acquire_inner_lock().exit_code;
-        if let Some(refmut) = translated_refmut(inner.get_user_token(), exit_code_ptr) {
-            *refmut = exit_code;
-            found_pid as isize
-        } else {
-            warn!("Illegal memory region in sys_waitpid!");
-            -1
+    let process = current_process();
+    loop {
+        // ---- hold current PCB lock
+        let mut inner = process.acquire_inner_lock();
+        let mut target = inner
+            .children
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| pid == -1 || pid as usize == p.getpid())
+            .peekable();
+        if target.peek().is_none() {
+            // specified child process not found
+            return -1;
         }
-    } else {
-        // no zombie process in specified child process(es)
-        -2
+        if let Some((index, _)) = target.find(|(_, p)| p.acquire_inner_lock().is_zombie) {
+            let child = inner.children.remove(index);
+            // confirm that child will be deallocated after removing from children list
+            assert_eq!(Arc::strong_count(&child), 1);
+            let found_pid = child.getpid();
+            let exit_code = child.acquire_inner_lock().exit_code.unwrap();
+            let token = inner.get_user_token();
+            // release current PCB lock, as writing to a copy-on-write page acquires it again
+            drop(inner);
+            if exit_code_ptr.is_null() {
+                return found_pid as isize;
+            }
+            return if let Some(refmut) = translated_refmut(token, exit_code_ptr) {
+                *refmut = exit_code;
+                found_pid as isize
+            } else {
+                warn!("Illegal memory region in sys_waitpid!");
+                -1
+            };
+        }
+        if options & WNOHANG != 0 {
+            return 0;
+        }
+        if !inner
+            .signals
+            .deliverable(&task.acquire_inner_lock())
+            .is_empty()
+        {
+            return -EINTR;
+        }
+        inner.child_exited.add_current();
+        drop(inner);
+        // ---- release current PCB lock
+        block_current_and_run_next();
     }
-    // ---- release current PCB lock automatically
 }
 
-pub fn sys_spawn(path: *const u8) -> isize {
+/// Return the pid of the new process, -EACCES if `path` is a directory, or -ENOEXEC if it
+/// is not a valid executable.
+pub fn sys_spawn(path: *const u8, args: *const usize, envs: *const usize) -> isize {
     let token = current_user_token();
-    if let Some(path) = translated_str(token, path) {
-        if let Some(elf_data) = get_app_data_by_name(&path) {
-            let current_task = current_task().unwrap();
-            let new_task = current_task.spawn_child(elf_data);
-            let new_pid = new_task.getpid();
-            add_task(new_task);
+    if let (Some(path), Some(args), Some(envs)) = (
+        translated_str(token, path),
+        translated_args(token, args),
+        translated_args(token, envs),
+    ) {
+        if args_size(&args) + args_size(&envs) > ARG_MAX {
+            warn!("Arguments too long in sys_spawn!");
+            return -1;
+        }
+        if let Ok(app_inode) =
+            cwd_inode().and_then(|dir| open_file_at(&dir, &path, OpenFlags::RDONLY))
+        {
+            if app_inode.inode().is_dir() {
+                return -EACCES;
+            }
+            let elf_data = app_inode.read_all();
+            let new_process = match current_process().spawn_child(elf_data.as_slice(), &args, &envs)
+            {
+                Some(new_process) => new_process,
+                None => return -ENOEXEC,
+            };
+            let new_pid = new_process.getpid();
+            add_task(new_process.main_task());
             new_pid as isize
         } else {
             warn!("No such application name.");
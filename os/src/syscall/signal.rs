@@ -0,0 +1,129 @@
+use crate::errno::{EFAULT, EINVAL, ESRCH};
+use crate::mm::{translated_ref, translated_refmut};
+use crate::task::{
+    current_process, current_task, current_user_token, pid2process, wake_up_task, SignalAction,
+    SignalFlags, MAX_SIG,
+};
+use alloc::vec::Vec;
+
+const SIG_BLOCK: usize = 0;
+const SIG_UNBLOCK: usize = 1;
+const SIG_SETMASK: usize = 2;
+
+/// Signal 0 sends nothing but checks whether the process exists.
+pub fn sys_kill(pid: isize, signum: usize) -> isize {
+    if pid <= 0 || signum > MAX_SIG {
+        return -EINVAL;
+    }
+    let process = match pid2process(pid as usize) {
+        Some(process) => process,
+        None => return -ESRCH,
+    };
+    if let Some(signal) = SignalFlags::from_signum(signum) {
+        let mut inner = process.acquire_inner_lock();
+        inner.signals.send(signal);
+        let tasks: Vec<_> = inner.tasks.iter().flatten().cloned().collect();
+        drop(inner);
+        // a blocked thread checks signals before going back to sleep, and a stopped one
+        // may be continued
+        for task in tasks {
+            wake_up_task(task);
+        }
+    }
+    0
+}
+
+/// Actions of SIGKILL and SIGSTOP cannot be changed.
+pub fn sys_sigaction(
+    signum: usize,
+    action: *const SignalAction,
+    old_action: *mut SignalAction,
+) -> isize {
+    let signal = match SignalFlags::from_signum(signum) {
+        Some(signal) => signal,
+        None => return -EINVAL,
+    };
+    let token = current_user_token();
+    let new = if action.is_null() {
+        None
+    } else {
+        if SignalFlags::unmaskable().contains(signal) {
+            return -EINVAL;
+        }
+        match translated_ref(token, action) {
+            Some(&action) => Some(SignalAction {
+                mask: action.mask - SignalFlags::unmaskable(),
+                ..action
+            }),
+            None => return -EFAULT,
+        }
+    };
+    let process = current_process();
+    let mut inner = process.acquire_inner_lock();
+    let old = inner.signals.actions[signum];
+    if let Some(new) = new {
+        inner.signals.actions[signum] = new;
+        // pending signals are discarded once ignored
+        if inner.signals.ignored(signal) {
+            inner.signals.pending.remove(signal);
+        }
+    }
+    drop(inner);
+    if !old_action.is_null() {
+        match translated_refmut(token, old_action) {
+            Some(refmut) => *refmut = old,
+            None => return -EFAULT,
+        }
+    }
+    0
+}
+
+pub fn sys_sigprocmask(how: usize, set: *const u32, old_set: *mut u32) -> isize {
+    let token = current_user_token();
+    let set = if set.is_null() {
+        None
+    } else {
+        match translated_ref(token, set) {
+            Some(&set) => Some(SignalFlags::from_bits_truncate(set) - SignalFlags::unmaskable()),
+            None => return -EFAULT,
+        }
+    };
+    let task = current_task().unwrap();
+    let mut task_inner = task.acquire_inner_lock();
+    let old = task_inner.signal_mask;
+    if let Some(set) = set {
+        task_inner.signal_mask = match how {
+            SIG_BLOCK => old | set,
+            SIG_UNBLOCK => old - set,
+            SIG_SETMASK => set,
+            _ => return -EINVAL,
+        };
+    }
+    drop(task_inner);
+    if !old_set.is_null() {
+        match translated_refmut(token, old_set) {
+            Some(refmut) => *refmut = old.bits(),
+            None => return -EFAULT,
+        }
+    }
+    0
+}
+
+/// Restore the user context saved before the handler was entered, return its a0 so that
+/// it is kept as it is, or -EINVAL if the thread is not running a handler.
+pub fn sys_sigreturn() -> isize {
+    let task = current_task().unwrap();
+    let mut task_inner = task.acquire_inner_lock();
+    match task_inner.signal_frame.take() {
+        Some((saved, mask)) => {
+            task_inner.signal_mask = mask;
+            // the kernel fields are those of the current thread, which may be a forked copy
+            let trap_ctx = task_inner.get_trap_ctx();
+            trap_ctx.x = saved.x;
+            trap_ctx.sstatus = saved.sstatus;
+            trap_ctx.sepc = saved.sepc;
+            trap_ctx.x[10] as isize
+        }
+        None => -EINVAL,
+    }
+}
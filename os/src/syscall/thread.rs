@@ -0,0 +1,70 @@
+use crate::errno::{EDEADLK, EFAULT, EINTR, ENOMEM, ESRCH};
+use crate::mm::translated_refmut;
+use crate::task::{
+    add_task, block_current_and_run_next, current_process, current_task, current_user_token,
+};
+
+/// Return the tid of the new thread, which starts from `entry` with `arg` in a0.
+pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
+    match current_process().create_thread(entry, arg) {
+        Some(task) => {
+            let tid = task.acquire_inner_lock().tid;
+            add_task(task);
+            tid as isize
+        }
+        None => -ENOMEM,
+    }
+}
+
+pub fn sys_gettid() -> isize {
+    current_task().unwrap().acquire_inner_lock().tid as isize
+}
+
+/// Block until thread `tid` exits, whose exit code is written to `exit_code_ptr` unless it
+/// is null, and return `tid`. The tid may be reused once the thread is joined.
+pub fn sys_waittid(tid: usize, exit_code_ptr: *mut i32) -> isize {
+    let task = current_task().unwrap();
+    if task.acquire_inner_lock().tid == tid {
+        return -EDEADLK;
+    }
+    let process = task.process.upgrade().unwrap();
+    loop {
+        // ---- hold current PCB lock
+        let mut inner = process.acquire_inner_lock();
+        let exit_code = match inner.tasks.get(tid) {
+            Some(Some(waited)) => {
+                let waited_inner = waited.acquire_inner_lock();
+                if waited_inner.is_zombie() {
+                    Some(waited_inner.exit_code)
+                } else {
+                    None
+                }
+            }
+            _ => return -ESRCH,
+        };
+        if let Some(exit_code) = exit_code {
+            inner.tasks[tid] = None;
+            inner.tid_allocator.dealloc(tid);
+            // release current PCB lock, as writing to a copy-on-write page acquires it again
+            drop(inner);
+            if !exit_code_ptr.is_null() {
+                match translated_refmut(current_user_token(), exit_code_ptr) {
+                    Some(refmut) => *refmut = exit_code,
+                    None => return -EFAULT,
+                }
+            }
+            return tid as isize;
+        }
+        if !inner
+            .signals
+            .deliverable(&task.acquire_inner_lock())
+            .is_empty()
+        {
+            return -EINTR;
+        }
+        inner.thread_exited.add_current();
+        drop(inner);
+        // ---- release current PCB lock
+        block_current_and_run_next();
+    }
+}
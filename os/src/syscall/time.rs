@@ -1,6 +1,9 @@
-use crate::mm::translated_byte_buffer_copy;
-use crate::task::current_user_token;
-use crate::timer::{get_time_us, USEC_PER_SEC};
+use crate::errno::{EFAULT, EINTR, EINVAL};
+use crate::mm::{translated_byte_buffer_copy, translated_ref};
+use crate::task::{
+    block_current_and_run_next, current_signal_pending, current_task, current_user_token,
+};
+use crate::timer::{add_timer, get_time_us, remove_timers, USEC_PER_SEC};
 use core::{mem, slice};
 
 #[repr(C)]
@@ -17,6 +20,27 @@ impl TimeVal {
     }
 }
 
+#[repr(C)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+impl TimeSpec {
+    fn as_bytes(&self) -> &[u8] {
+        let len = mem::size_of::<TimeSpec>();
+        let data = self as *const _ as usize as *const u8;
+        unsafe { slice::from_raw_parts(data, len) }
+    }
+}
+
+const NSEC_PER_USEC: usize = 1000;
+const NSEC_PER_SEC: usize = 1000000000;
+const CLOCK_REALTIME: usize = 0;
+const CLOCK_MONOTONIC: usize = 1;
+/// The request is an absolute time rather than an interval.
+const TIMER_ABSTIME: usize = 1;
+
 pub fn sys_get_time(ts: *mut TimeVal, _tz: usize) -> isize {
     let tims_us = get_time_us();
     let time_val = TimeVal {
@@ -37,3 +61,91 @@ pub fn sys_get_time(ts: *mut TimeVal, _tz: usize) -> isize {
         -1
     }
 }
+
+/// Block the current task until `deadline` in microseconds, return false if it is
+/// interrupted by a signal.
+fn sleep_until(deadline: usize) -> bool {
+    let task = current_task().unwrap();
+    while get_time_us() < deadline {
+        if current_signal_pending() {
+            return false;
+        }
+        add_timer(deadline, task.clone());
+        block_current_and_run_next();
+        if get_time_us() < deadline {
+            remove_timers(&task);
+        }
+    }
+    true
+}
+
+/// Return the requested time in microseconds, rounded up, or EINVAL if it does not fit.
+fn read_timespec(req: *const TimeSpec) -> Result<usize, isize> {
+    let req = translated_ref(current_user_token(), req).ok_or(EFAULT)?;
+    if req.nsec >= NSEC_PER_SEC {
+        return Err(EINVAL);
+    }
+    req.sec
+        .checked_mul(USEC_PER_SEC)
+        .and_then(|usec| usec.checked_add((req.nsec + NSEC_PER_USEC - 1) / NSEC_PER_USEC))
+        .ok_or(EINVAL)
+}
+
+/// Sleep for `interval` in microseconds, the time left is written to `rem` if it is
+/// interrupted by a signal.
+fn sleep_for(interval: usize, rem: *mut TimeSpec) -> isize {
+    // an interval too long to end is as good as forever
+    let deadline = get_time_us().saturating_add(interval);
+    if sleep_until(deadline) {
+        return 0;
+    }
+    if !rem.is_null() {
+        let left = deadline.saturating_sub(get_time_us());
+        let left = TimeSpec {
+            sec: left / USEC_PER_SEC,
+            nsec: left % USEC_PER_SEC * NSEC_PER_USEC,
+        };
+        let rem = rem as *mut u8;
+        if translated_byte_buffer_copy(
+            current_user_token(),
+            rem,
+            mem::size_of::<TimeSpec>(),
+            left.as_bytes(),
+        )
+        .is_none()
+        {
+            return -EFAULT;
+        }
+    }
+    -EINTR
+}
+
+pub fn sys_nanosleep(req: *const TimeSpec, rem: *mut TimeSpec) -> isize {
+    match read_timespec(req) {
+        Ok(interval) => sleep_for(interval, rem),
+        Err(errno) => -errno,
+    }
+}
+
+/// Both clocks count from boot, an absolute sleep leaves `rem` alone.
+pub fn sys_clock_nanosleep(
+    clock_id: usize,
+    flags: usize,
+    req: *const TimeSpec,
+    rem: *mut TimeSpec,
+) -> isize {
+    if clock_id != CLOCK_REALTIME && clock_id != CLOCK_MONOTONIC {
+        return -EINVAL;
+    }
+    match read_timespec(req) {
+        Ok(time) if flags & TIMER_ABSTIME != 0 => {
+            if sleep_until(time) {
+                0
+            } else {
+                -EINTR
+            }
+        }
+        Ok(interval) => sleep_for(interval, rem),
+        Err(errno) => -errno,
+    }
+}
@@ -1,5 +1,8 @@
-use super::task::TaskControlBlock;
-use alloc::{collections::BinaryHeap, sync::Arc};
+use super::{process::ProcessControlBlock, task::TaskControlBlock};
+use alloc::{
+    collections::{BTreeMap, BinaryHeap},
+    sync::Arc,
+};
 use lazy_static::lazy_static;
 use spin::Mutex;
 
@@ -26,6 +29,9 @@ impl TaskManager {
 
 lazy_static! {
     static ref TASK_MANAGER: Mutex<TaskManager> = Mutex::new(TaskManager::new());
+    /// Processes which have not exited, for signals to find them by pid.
+    static ref PID2PROCESS: Mutex<BTreeMap<usize, Arc<ProcessControlBlock>>> =
+        Mutex::new(BTreeMap::new());
 }
 
 pub fn add_task(task: Arc<TaskControlBlock>) {
@@ -36,6 +42,18 @@ pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
     TASK_MANAGER.lock().fetch()
 }
 
+pub fn insert_into_pid2process(pid: usize, process: Arc<ProcessControlBlock>) {
+    PID2PROCESS.lock().insert(pid, process);
+}
+
+pub fn remove_from_pid2process(pid: usize) {
+    PID2PROCESS.lock().remove(&pid);
+}
+
+pub fn pid2process(pid: usize) -> Option<Arc<ProcessControlBlock>> {
+    PID2PROCESS.lock().get(&pid).cloned()
+}
+
 const BIT_STRIDE: usize = 65536;
 
 struct TaskControlBlockQueuer {
@@ -1,22 +1,32 @@
 mod context;
 mod manager;
 mod pid;
+mod process;
 mod processor;
+mod signal;
 mod switch;
 mod task;
+mod wait_queue;
 
-use crate::loader::get_app_data_by_name;
-use crate::mm::{MapPermission, VirtAddr};
-use alloc::sync::Arc;
+use crate::fs::{open_file, OpenFlags};
+use crate::mm::{AccessType, Page, VirtPageNum};
+use alloc::{sync::Arc, vec::Vec};
 use context::TaskContext;
 use lazy_static::lazy_static;
+use manager::{insert_into_pid2process, remove_from_pid2process};
+use process::ProcessControlBlock;
 use processor::schedule;
-use task::{TaskControlBlock, TaskStatus};
+use signal::{DefaultAction, SIG_DFL, SIG_IGN};
+use task::TaskStatus;
 
-pub use manager::add_task;
+pub use manager::{add_task, pid2process};
 pub use processor::{
-    current_task, current_trap_ctx, current_user_token, run_tasks, take_current_task,
+    current_process, current_task, current_trap_ctx, current_trap_ctx_va, current_user_token,
+    run_tasks, take_current_task,
 };
+pub use signal::{SignalAction, SignalFlags, MAX_SIG};
+pub use task::TaskControlBlock;
+pub use wait_queue::WaitQueue;
 
 pub fn suspend_current_and_run_next() {
     // There must be an application running.
@@ -36,36 +46,112 @@ pub fn suspend_current_and_run_next() {
     schedule(task_ctx_ptr2);
 }
 
+/// Block the current task until it is woken up by `wake_up_task`.
+pub fn block_current_and_run_next() {
+    let task = take_current_task().unwrap();
+    let mut task_inner = task.acquire_inner_lock();
+    let task_ctx_ptr2 = task_inner.get_task_ctx_ptr2();
+    task_inner.task_status = TaskStatus::Blocked;
+    drop(task_inner);
+    // the task is kept alive by whoever is going to wake it up
+    drop(task);
+    schedule(task_ctx_ptr2);
+}
+
+/// Put a blocked task back to the ready queue, while other tasks are left alone.
+pub fn wake_up_task(task: Arc<TaskControlBlock>) {
+    let mut task_inner = task.acquire_inner_lock();
+    if task_inner.task_status == TaskStatus::Blocked {
+        task_inner.task_status = TaskStatus::Ready;
+        drop(task_inner);
+        add_task(task);
+    }
+}
+
+/// Exit the current thread, and the whole process if it is the main thread.
 pub fn exit_current_and_run_next(exit_code: i32) {
+    let is_main = current_task().unwrap().acquire_inner_lock().tid == 0;
+    exit_current(exit_code, is_main);
+}
+
+/// Exit the current thread. If `whole_process`, other threads are killed, and the
+/// process exits with `exit_code` once all of them exit.
+fn exit_current(exit_code: i32, whole_process: bool) {
     // take from Processor
     let task = take_current_task().unwrap();
-    if task.getpid() == 0 {
-        panic!("initproc exited!");
-    }
+    let process = task.process.upgrade().unwrap();
     // **** hold current PCB lock
+    let mut process_inner = process.acquire_inner_lock();
+    let mut others = Vec::new();
+    if whole_process && process_inner.exit_code.is_none() {
+        if process.getpid() == 0 {
+            panic!("initproc exited!");
+        }
+        process_inner.exit_code = Some(exit_code);
+        // other threads exit on their way back to user mode
+        process_inner.signals.pending.insert(SignalFlags::SIGKILL);
+        others = process_inner
+            .tasks
+            .iter()
+            .flatten()
+            .filter(|other| !Arc::ptr_eq(other, &task))
+            .cloned()
+            .collect();
+    }
     let mut task_inner = task.acquire_inner_lock();
     // Change status to Zombie
     task_inner.task_status = TaskStatus::Zombie;
     // Record exit code
     task_inner.exit_code = exit_code;
-    // do not move to its parent but under initproc
-
-    // ++++++ hold initproc PCB lock here
+    let tid = task_inner.tid;
+    let user_stack = task_inner.user_stack.take();
+    drop(task_inner);
+    process_inner.dealloc_user_res(tid, user_stack);
+    process_inner.thread_exited.wake_all();
+    let mut parent = None;
+    if process_inner.exit_code.is_some()
+        && process_inner
+            .tasks
+            .iter()
+            .flatten()
+            .all(|t| t.acquire_inner_lock().is_zombie())
     {
-        let mut initproc_inner = INITPROC.acquire_inner_lock();
-        for child in &task_inner.children {
-            child.acquire_inner_lock().parent = Some(Arc::downgrade(&INITPROC));
-            initproc_inner.children.push(child.clone());
+        remove_from_pid2process(process.getpid());
+        process_inner.is_zombie = true;
+        // do not move to its parent but under initproc
+
+        // ++++++ hold initproc PCB lock here
+        {
+            let mut initproc_inner = INITPROC.acquire_inner_lock();
+            for child in &process_inner.children {
+                child.acquire_inner_lock().parent = Some(Arc::downgrade(&INITPROC));
+                initproc_inner.children.push(child.clone());
+            }
+            // some of them may be zombies already
+            if !process_inner.children.is_empty() {
+                initproc_inner.child_exited.wake_all();
+            }
         }
-    }
-    // ++++++ release parent PCB lock here
+        // ++++++ release parent PCB lock here
 
-    task_inner.children.clear();
-    // deallocate user space
-    task_inner.memory_set.recycle_data_pages();
-    drop(task_inner);
+        process_inner.children.clear();
+        // deallocate user space
+        process_inner.memory_set.recycle_data_pages();
+        parent = process_inner
+            .parent
+            .as_ref()
+            .and_then(|parent| parent.upgrade());
+    }
+    drop(process_inner);
     // **** release current PCB lock
+    for other in others {
+        wake_up_task(other);
+    }
+    if let Some(parent) = parent {
+        parent.acquire_inner_lock().child_exited.wake_all();
+    }
     // drop task manually to maintain rc correctly
+    drop(process);
     drop(task);
     // we do not have to save task context
     let unused: usize = 0;
@@ -81,35 +167,119 @@ pub fn set_current_prio(prio: isize) -> isize {
     }
 }
 
-pub fn current_insert_framed_area(
-    start_va: VirtAddr,
-    end_va: VirtAddr,
-    permission: MapPermission,
-) -> Result<(), &'static str> {
-    current_task()
-        .unwrap()
+/// Resolve a page fault of current task, return false if it is a segmentation fault.
+pub fn current_handle_page_fault(vpn: VirtPageNum, access: AccessType) -> bool {
+    current_process()
         .acquire_inner_lock()
         .memory_set
-        .insert_framed_area(start_va, end_va, permission)
+        .handle_page_fault(vpn, access)
 }
 
-pub fn current_delete_framed_area(
-    start_va: VirtAddr,
-    end_va: VirtAddr,
-) -> Result<(), &'static str> {
-    current_task()
-        .unwrap()
+/// Return the page of current task at `vpn`, see `MemorySet::page`.
+pub fn current_page(vpn: VirtPageNum) -> Option<Arc<Page>> {
+    current_process().acquire_inner_lock().memory_set.page(vpn)
+}
+
+pub fn current_set_brk(brk: usize) -> usize {
+    current_process()
         .acquire_inner_lock()
         .memory_set
-        .delete_framed_area(start_va, end_va)
+        .set_brk(brk)
 }
 
 lazy_static! {
-    static ref INITPROC: Arc<TaskControlBlock> = Arc::new(TaskControlBlock::new(
-        get_app_data_by_name("initproc").unwrap()
-    ));
+    static ref INITPROC: Arc<ProcessControlBlock> = {
+        let inode = open_file("initproc", OpenFlags::RDONLY).unwrap();
+        let elf_data = inode.read_all();
+        ProcessControlBlock::new(elf_data.as_slice(), &[], &[]).unwrap()
+    };
 }
 
 pub fn add_initproc() {
-    add_task(INITPROC.clone());
+    insert_into_pid2process(INITPROC.getpid(), INITPROC.clone());
+    add_task(INITPROC.main_task());
+}
+
+/// Whether a signal interrupts blocking syscalls of the current thread.
+pub fn current_signal_pending() -> bool {
+    let task = current_task().unwrap();
+    let process = task.process.upgrade().unwrap();
+    let inner = process.acquire_inner_lock();
+    let pending = !inner
+        .signals
+        .deliverable(&task.acquire_inner_lock())
+        .is_empty();
+    pending
+}
+
+/// Whether the current process is about to be terminated by a signal, so that the
+/// current thread should stop blocking even if it cannot be interrupted.
+pub fn current_killed() -> bool {
+    let task = current_task().unwrap();
+    let process = task.process.upgrade().unwrap();
+    let inner = process.acquire_inner_lock();
+    let killed = inner.signals.fatal(&task.acquire_inner_lock());
+    killed
+}
+
+/// Send a signal to the current process.
+pub fn current_send_signal(signal: SignalFlags) {
+    current_process().acquire_inner_lock().signals.send(signal);
+}
+
+/// Send a signal caused by a fault of the current thread.
+pub fn current_force_signal(signal: SignalFlags) {
+    let task = current_task().unwrap();
+    let process = task.process.upgrade().unwrap();
+    let mut inner = process.acquire_inner_lock();
+    inner.signals.force(signal, &mut task.acquire_inner_lock());
+}
+
+/// Deliver pending signals before returning to user mode, the current thread never returns
+/// if the process is terminated.
+pub fn handle_signals() {
+    loop {
+        let task = current_task().unwrap();
+        let process = task.process.upgrade().unwrap();
+        let mut inner = process.acquire_inner_lock();
+        let mut task_inner = task.acquire_inner_lock();
+        let (signum, action) = match inner.signals.next(&task_inner) {
+            Some(next) => next,
+            None if inner.signals.stopped => {
+                drop(task_inner);
+                drop(inner);
+                drop(process);
+                drop(task);
+                // woken up by signals sent
+                block_current_and_run_next();
+                continue;
+            }
+            None => return,
+        };
+        let signal = SignalFlags::from_signum(signum).unwrap();
+        match action.handler {
+            SIG_IGN => {}
+            SIG_DFL => match DefaultAction::of(signal) {
+                DefaultAction::Terminate => {
+                    drop(task_inner);
+                    drop(inner);
+                    drop(process);
+                    drop(task);
+                    exit_current(-(signum as i32), true);
+                }
+                DefaultAction::Stop => inner.signals.stopped = true,
+                DefaultAction::Ignore | DefaultAction::Continue => {}
+            },
+            handler => {
+                // the handler returns to the restorer, which calls sigreturn
+                let trap_ctx = task_inner.get_trap_ctx();
+                task_inner.signal_frame = Some((trap_ctx.clone(), task_inner.signal_mask));
+                task_inner.signal_mask |= action.mask | signal;
+                trap_ctx.sepc = handler;
+                trap_ctx.x[1] = action.restorer;
+                trap_ctx.x[10] = signum;
+                return;
+            }
+        }
+    }
 }
@@ -6,43 +6,45 @@ use alloc::vec::Vec;
 use lazy_static::lazy_static;
 use spin::Mutex;
 
-struct PidAllocator {
+/// Allocate ids from 0, where freed ones are reused first.
+pub struct RecycleAllocator {
     current: usize,
     recycled: Vec<usize>,
 }
 
-impl PidAllocator {
-    fn new() -> Self {
+impl RecycleAllocator {
+    pub fn new() -> Self {
         Self {
             current: 0,
             recycled: Vec::new(),
         }
     }
 
-    fn alloc(&mut self) -> PidHandle {
-        if let Some(pid) = self.recycled.pop() {
-            PidHandle(pid)
+    pub fn alloc(&mut self) -> usize {
+        if let Some(id) = self.recycled.pop() {
+            id
         } else {
             assert!(self.current < usize::MAX);
             self.current += 1;
-            PidHandle(self.current - 1)
+            self.current - 1
         }
     }
 
-    fn dealloc(&mut self, pid: usize) {
-        if pid >= self.current || self.recycled.contains(&pid) {
-            panic!("pid {} has not been allocated!", pid);
+    pub fn dealloc(&mut self, id: usize) {
+        if id >= self.current || self.recycled.contains(&id) {
+            panic!("id {} has not been allocated!", id);
         }
-        self.recycled.push(pid);
+        self.recycled.push(id);
     }
 }
 
 lazy_static! {
-    static ref PID_ALLOCATOR: Mutex<PidAllocator> = Mutex::new(PidAllocator::new());
+    static ref PID_ALLOCATOR: Mutex<RecycleAllocator> = Mutex::new(RecycleAllocator::new());
+    static ref KSTACK_ALLOCATOR: Mutex<RecycleAllocator> = Mutex::new(RecycleAllocator::new());
 }
 
 pub fn pid_alloc() -> PidHandle {
-    PID_ALLOCATOR.lock().alloc()
+    PidHandle(PID_ALLOCATOR.lock().alloc())
 }
 
 pub struct PidHandle(pub usize);
@@ -53,21 +55,22 @@ impl Drop for PidHandle {
     }
 }
 
+/// A kernel stack of a thread, which has an id of its own.
 pub struct KernelStack {
-    pid: usize,
+    id: usize,
 }
 
 impl KernelStack {
     /// Return (bottom, top) of a kernel stack in kernel space.
-    fn position(pid: usize) -> (usize, usize) {
-        let top = TRAMPOLINE - pid * (KERNEL_STACK_SIZE + PAGE_SIZE);
+    fn position(id: usize) -> (usize, usize) {
+        let top = TRAMPOLINE - id * (KERNEL_STACK_SIZE + PAGE_SIZE);
         let buttom = top - KERNEL_STACK_SIZE;
         (buttom, top)
     }
 
-    pub fn new(pid_handle: &PidHandle) -> Self {
-        let pid = pid_handle.0;
-        let (kernel_stack_bottom, kernel_stack_top) = Self::position(pid);
+    pub fn new() -> Self {
+        let id = KSTACK_ALLOCATOR.lock().alloc();
+        let (kernel_stack_bottom, kernel_stack_top) = Self::position(id);
         KERNEL_SPACE
             .lock()
             .insert_framed_area(
@@ -76,7 +79,7 @@ impl KernelStack {
                 MapPermission::R | MapPermission::W,
             )
             .unwrap();
-        Self { pid }
+        Self { id }
     }
 
     pub fn push_on_top<T>(&self, value: T) -> *mut T {
@@ -89,11 +92,11 @@ impl KernelStack {
     }
 
     pub fn get_bottom(&self) -> usize {
-        Self::position(self.pid).0
+        Self::position(self.id).0
     }
 
     pub fn get_top(&self) -> usize {
-        Self::position(self.pid).1
+        Self::position(self.id).1
     }
 }
 
@@ -104,5 +107,6 @@ impl Drop for KernelStack {
             .lock()
             .remove_area_with_start_vpn(kernel_stack_bottom_va.into())
             .unwrap();
+        KSTACK_ALLOCATOR.lock().dealloc(self.id);
     }
 }
@@ -0,0 +1,415 @@
+use super::{
+    current_task,
+    manager::insert_into_pid2process,
+    pid::{pid_alloc, PidHandle, RecycleAllocator},
+    signal::Signals,
+    task::{trap_ctx_va, TaskControlBlock},
+    WaitQueue,
+};
+use crate::{
+    config::{MAX_FD_NUM, PAGE_SIZE, USER_STACK_LIMIT},
+    errno::{EBUSY, ENOEXEC},
+    fs::{File, FileDescriptor, STDIN, STDOUT},
+    mm::{MapPermission, MemorySet, VirtAddr, AT_NULL, AT_RANDOM},
+    timer::get_time_us,
+    trap::TrapContext,
+};
+use alloc::{
+    string::String,
+    sync::{Arc, Weak},
+    vec,
+    vec::Vec,
+};
+use core::{mem, slice};
+use spin::{Mutex, MutexGuard};
+
+pub struct ProcessControlBlockInner {
+    /// Set once all threads have exited.
+    pub is_zombie: bool,
+    pub memory_set: MemorySet,
+    pub base_size: usize,
+    pub parent: Option<Weak<ProcessControlBlock>>,
+    pub children: Vec<Arc<ProcessControlBlock>>,
+    /// Threads wait here for children to exit.
+    pub child_exited: WaitQueue,
+    /// Set when the process starts to exit, which is done once all threads exit.
+    pub exit_code: Option<i32>,
+    pub fd_table: Vec<Option<FileDescriptor>>,
+    /// An absolute path without `.` or `..` in it.
+    pub cwd: String,
+    pub signals: Signals,
+    /// Threads indexed by tid, where exited ones stay until joined.
+    pub tasks: Vec<Option<Arc<TaskControlBlock>>>,
+    pub tid_allocator: RecycleAllocator,
+    /// Threads wait here for other threads to exit.
+    pub thread_exited: WaitQueue,
+}
+
+impl ProcessControlBlockInner {
+    pub fn get_user_token(&self) -> usize {
+        self.memory_set.page_table.token()
+    }
+
+    pub fn get_file(&self, fd: usize) -> Option<Arc<dyn File>> {
+        self.fd_table.get(fd)?.as_ref().map(|fd| fd.file.clone())
+    }
+
+    /// Lay out the initial user stack as System V does: from sp upwards are argc, argv,
+    /// envp and the auxiliary vector, followed by the strings they point to.
+    /// argc, argv and envp are also passed to the entry point in a0-a2.
+    pub fn init_user_stack(
+        &mut self,
+        trap_ctx: &mut TrapContext,
+        args: &[String],
+        envs: &[String],
+        auxv: &[(usize, usize)],
+    ) {
+        let memory_set = &mut self.memory_set;
+        let mut user_sp = trap_ctx.x[2];
+        let mut push_str = |s: &String| {
+            let mut data = s.clone().into_bytes();
+            data.push(0);
+            user_sp -= data.len();
+            memory_set.copy_to_user(user_sp, &data);
+            user_sp
+        };
+        let argv: Vec<usize> = args.iter().map(&mut push_str).collect();
+        let envp: Vec<usize> = envs.iter().map(&mut push_str).collect();
+        let random = random_bytes();
+        user_sp -= random.len();
+        memory_set.copy_to_user(user_sp, &random);
+        let mut words: Vec<usize> = vec![args.len()];
+        words.extend(argv);
+        words.push(0);
+        words.extend(envp);
+        words.push(0);
+        for &(type_, value) in auxv.iter().chain(&[(AT_RANDOM, user_sp), (AT_NULL, 0)]) {
+            words.push(type_);
+            words.push(value);
+        }
+        user_sp -= words.len() * mem::size_of::<usize>();
+        // keep sp aligned to 16 bytes as required by the calling convention
+        user_sp -= user_sp % 16;
+        let data = unsafe {
+            slice::from_raw_parts(
+                words.as_ptr() as *const u8,
+                words.len() * mem::size_of::<usize>(),
+            )
+        };
+        memory_set.copy_to_user(user_sp, data);
+        trap_ctx.x[2] = user_sp;
+        trap_ctx.x[10] = args.len();
+        trap_ctx.x[11] = user_sp + mem::size_of::<usize>();
+        trap_ctx.x[12] = user_sp + (args.len() + 2) * mem::size_of::<usize>();
+    }
+
+    /// Return None if all of the `MAX_FD_NUM` fds are in use.
+    pub fn alloc_fd(&mut self) -> Option<usize> {
+        if let Some(fd) = self.fd_table.iter().position(|f| f.is_none()) {
+            Some(fd)
+        } else if self.fd_table.len() < MAX_FD_NUM {
+            self.fd_table.push(None);
+            Some(self.fd_table.len() - 1)
+        } else {
+            None
+        }
+    }
+
+    /// Allocate a tid, a user stack and a trap context page for a new thread,
+    /// return the tid and the bottom of the stack, or None if there is no room for it.
+    fn alloc_user_res(&mut self) -> Option<(usize, usize)> {
+        let start: usize = self
+            .memory_set
+            .find_free_range(USER_STACK_LIMIT + PAGE_SIZE)?
+            .into();
+        let user_stack = start + PAGE_SIZE;
+        // a page without access is kept below the stack to catch overflows
+        self.memory_set
+            .insert_lazy_area(start.into(), user_stack.into(), MapPermission::U)
+            .unwrap();
+        self.memory_set
+            .insert_lazy_area(
+                user_stack.into(),
+                (user_stack + USER_STACK_LIMIT).into(),
+                MapPermission::R | MapPermission::W | MapPermission::U,
+            )
+            .unwrap();
+        let tid = self.tid_allocator.alloc();
+        let trap_ctx = trap_ctx_va(tid);
+        self.memory_set
+            .insert_framed_area(
+                trap_ctx.into(),
+                (trap_ctx + PAGE_SIZE).into(),
+                MapPermission::R | MapPermission::W,
+            )
+            .unwrap();
+        Some((tid, user_stack))
+    }
+
+    /// Free the user stack and the trap context page of an exited thread, while those of
+    /// the main thread go with the address space.
+    pub fn dealloc_user_res(&mut self, tid: usize, user_stack: Option<usize>) {
+        if let Some(user_stack) = user_stack {
+            let (start, end) = user_stack_range(user_stack);
+            self.memory_set.remove_range(start, end);
+        }
+        if tid != 0 {
+            self.memory_set
+                .remove_area_with_start_vpn(VirtAddr::from(trap_ctx_va(tid)).into())
+                .unwrap();
+        }
+    }
+
+    /// Whether threads other than `task` are running.
+    fn has_other_tasks(&self, task: &Arc<TaskControlBlock>) -> bool {
+        self.tasks
+            .iter()
+            .flatten()
+            .any(|t| !Arc::ptr_eq(t, task) && !t.acquire_inner_lock().is_zombie())
+    }
+}
+
+/// The range of the user stack from `user_stack`, with the guard page below it.
+fn user_stack_range(user_stack: usize) -> (VirtAddr, VirtAddr) {
+    (
+        (user_stack - PAGE_SIZE).into(),
+        (user_stack + USER_STACK_LIMIT).into(),
+    )
+}
+
+/// A process, which owns the address space and the files shared by its threads.
+pub struct ProcessControlBlock {
+    // immutable
+    pub pid: PidHandle,
+    // mutable
+    inner: Mutex<ProcessControlBlockInner>,
+}
+
+impl ProcessControlBlock {
+    pub fn acquire_inner_lock(&self) -> MutexGuard<ProcessControlBlockInner> {
+        self.inner.lock()
+    }
+
+    pub fn getpid(&self) -> usize {
+        self.pid.0
+    }
+
+    /// The thread with tid 0, which is the one to run first.
+    pub fn main_task(&self) -> Arc<TaskControlBlock> {
+        self.acquire_inner_lock().tasks[0].clone().unwrap()
+    }
+
+    fn new_inner(memory_set: MemorySet, base_size: usize) -> ProcessControlBlockInner {
+        ProcessControlBlockInner {
+            is_zombie: false,
+            memory_set,
+            base_size,
+            parent: None,
+            children: Vec::new(),
+            child_exited: WaitQueue::new(),
+            exit_code: None,
+            fd_table: vec![
+                Some(FileDescriptor::new(Arc::new(STDIN), false)),
+                Some(FileDescriptor::new(Arc::new(STDOUT), false)),
+                Some(FileDescriptor::new(Arc::new(STDOUT), false)),
+            ],
+            cwd: String::from("/"),
+            signals: Signals::new(),
+            tasks: Vec::new(),
+            tid_allocator: RecycleAllocator::new(),
+            thread_exited: WaitQueue::new(),
+        }
+    }
+
+    /// Return None if `elf_data` is not a valid executable.
+    pub fn new(elf_data: &[u8], args: &[String], envs: &[String]) -> Option<Arc<Self>> {
+        // memory_set with elf program headers/trampoline/trap context/user stack
+        let (memory_set, user_sp, entry_point, auxv) = MemorySet::from_elf(elf_data)?;
+        let process = Arc::new(Self {
+            pid: pid_alloc(),
+            inner: Mutex::new(Self::new_inner(memory_set, user_sp)),
+        });
+        // create the main thread, whose trap context is in the new space
+        let mut inner = process.acquire_inner_lock();
+        let tid = inner.tid_allocator.alloc();
+        let task = Arc::new(TaskControlBlock::new(&process, &inner, tid, None));
+        let trap_ctx = task.acquire_inner_lock().get_trap_ctx();
+        *trap_ctx =
+            TrapContext::app_init_context(entry_point, user_sp, task.kernel_stack.get_top());
+        inner.init_user_stack(trap_ctx, args, envs, &auxv);
+        inner.tasks.push(Some(task));
+        drop(inner);
+        Some(process)
+    }
+
+    /// Only the calling thread is copied, which becomes the main thread of the child
+    /// with its signal mask and the handler it is running.
+    pub fn fork(self: &Arc<Self>) -> Arc<Self> {
+        let task = current_task().unwrap();
+        let task_inner = task.acquire_inner_lock();
+        let tid = task_inner.tid;
+        let trap_ctx = task_inner.get_trap_ctx().clone();
+        let signal_mask = task_inner.signal_mask;
+        let signal_frame = task_inner.signal_frame.clone();
+        drop(task_inner);
+        // ---- hold parent PCB lock
+        let mut parent_inner = self.acquire_inner_lock();
+        // copy user space (include trap context), sharing writable pages by copy-on-write
+        let mut memory_set = MemorySet::from_existed_user(&mut parent_inner.memory_set);
+        for other in parent_inner.tasks.iter().flatten() {
+            let other = other.acquire_inner_lock();
+            if other.tid != 0 && !other.is_zombie() {
+                memory_set
+                    .remove_area_with_start_vpn(VirtAddr::from(trap_ctx_va(other.tid)).into())
+                    .unwrap();
+            }
+            // stacks of the threads not copied are gone, while that of the calling one
+            // is used by the main thread of the child
+            if other.tid != tid {
+                if let Some(user_stack) = other.user_stack {
+                    let (start, end) = user_stack_range(user_stack);
+                    memory_set.remove_range(start, end);
+                }
+            }
+        }
+        let child = Arc::new(Self {
+            pid: pid_alloc(),
+            inner: Mutex::new(ProcessControlBlockInner {
+                parent: Some(Arc::downgrade(self)),
+                fd_table: parent_inner.fd_table.clone(),
+                cwd: parent_inner.cwd.clone(),
+                signals: parent_inner.signals.fork(),
+                ..Self::new_inner(memory_set, parent_inner.base_size)
+            }),
+        });
+        // add child
+        parent_inner.children.push(child.clone());
+        insert_into_pid2process(child.getpid(), child.clone());
+        drop(parent_inner);
+        // ---- release parent PCB lock
+        let mut child_inner = child.acquire_inner_lock();
+        let tid = child_inner.tid_allocator.alloc();
+        let task = Arc::new(TaskControlBlock::new(&child, &child_inner, tid, None));
+        let mut task_inner = task.acquire_inner_lock();
+        // the copied context goes on with its own kernel stack
+        *task_inner.get_trap_ctx() = TrapContext {
+            kernel_sp: task.kernel_stack.get_top(),
+            ..trap_ctx
+        };
+        task_inner.signal_mask = signal_mask;
+        task_inner.signal_frame = signal_frame;
+        drop(task_inner);
+        child_inner.tasks.push(Some(task));
+        drop(child_inner);
+        child
+    }
+
+    /// Only a process without other threads running can exec, return EBUSY otherwise,
+    /// or ENOEXEC if `elf_data` is not a valid executable.
+    /// Threads which have exited are gone, and the calling one becomes the main thread.
+    pub fn exec(&self, elf_data: &[u8], args: &[String], envs: &[String]) -> Result<(), isize> {
+        let task = current_task().unwrap();
+        // memory_set with elf program headers/trampoline/trap context/user stack
+        let (memory_set, user_sp, entry_point, auxv) =
+            MemorySet::from_elf(elf_data).ok_or(ENOEXEC)?;
+        // **** hold current PCB lock
+        let mut inner = self.acquire_inner_lock();
+        if inner.has_other_tasks(&task) {
+            return Err(EBUSY);
+        }
+        inner.tasks.clear();
+        inner.tid_allocator = RecycleAllocator::new();
+        let tid = inner.tid_allocator.alloc();
+        // substitute memory_set
+        inner.memory_set = memory_set;
+        inner.signals.exec();
+        // close fds marked close-on-exec
+        for fd in inner.fd_table.iter_mut() {
+            if fd.as_ref().map_or(false, |fd| fd.cloexec) {
+                fd.take();
+            }
+        }
+        // update trap_ctx ppn and initialize trap_ctx
+        let mut task_inner = task.acquire_inner_lock();
+        task_inner.tid = tid;
+        task_inner.user_stack = None;
+        // the handler running is gone with the old program, while the mask is kept
+        task_inner.signal_frame = None;
+        task_inner.trap_ctx_ppn = inner
+            .memory_set
+            .translate(VirtAddr::from(trap_ctx_va(tid)).into())
+            .unwrap()
+            .ppn();
+        let trap_ctx = task_inner.get_trap_ctx();
+        drop(task_inner);
+        *trap_ctx =
+            TrapContext::app_init_context(entry_point, user_sp, task.kernel_stack.get_top());
+        inner.init_user_stack(trap_ctx, args, envs, &auxv);
+        inner.tasks.push(Some(task));
+        Ok(())
+        // **** release current PCB lock
+    }
+
+    /// Return None if `elf_data` is not a valid executable.
+    pub fn spawn_child(
+        self: &Arc<Self>,
+        elf_data: &[u8],
+        args: &[String],
+        envs: &[String],
+    ) -> Option<Arc<Self>> {
+        let child = Self::new(elf_data, args, envs)?;
+        // ---- hold parent PCB lock
+        let mut parent_inner = self.acquire_inner_lock();
+        // **** acquire child PCB lock
+        let mut inner = child.acquire_inner_lock();
+        inner.parent = Some(Arc::downgrade(self));
+        inner.cwd = parent_inner.cwd.clone();
+        drop(inner);
+        // **** release child PCB lock
+        parent_inner.children.push(child.clone());
+        insert_into_pid2process(child.getpid(), child.clone());
+        Some(child)
+        // ---- release parent PCB lock
+    }
+
+    /// Create a thread running from `entry` with `arg` in a0 on a stack of its own,
+    /// which inherits the signal mask of the calling thread.
+    /// Return None if there is no room for the stack.
+    pub fn create_thread(
+        self: &Arc<Self>,
+        entry: usize,
+        arg: usize,
+    ) -> Option<Arc<TaskControlBlock>> {
+        let mut inner = self.acquire_inner_lock();
+        let (tid, user_stack) = inner.alloc_user_res()?;
+        let task = Arc::new(TaskControlBlock::new(self, &inner, tid, Some(user_stack)));
+        let signal_mask = current_task().unwrap().acquire_inner_lock().signal_mask;
+        task.acquire_inner_lock().signal_mask = signal_mask;
+        let trap_ctx = task.acquire_inner_lock().get_trap_ctx();
+        *trap_ctx = TrapContext::app_init_context(
+            entry,
+            user_stack + USER_STACK_LIMIT,
+            task.kernel_stack.get_top(),
+        );
+        trap_ctx.x[10] = arg;
+        if tid == inner.tasks.len() {
+            inner.tasks.push(Some(task.clone()));
+        } else {
+            inner.tasks[tid] = Some(task.clone());
+        }
+        Some(task)
+    }
+}
+
+/// Bytes for AT_RANDOM, mixed from the current time by xorshift.
+fn random_bytes() -> [u8; 16] {
+    let mut x = get_time_us() as u64 | 1;
+    let mut bytes = [0u8; 16];
+    for chunk in bytes.chunks_mut(8) {
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        chunk.copy_from_slice(&x.to_le_bytes());
+    }
+    bytes
+}
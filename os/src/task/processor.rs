@@ -1,9 +1,14 @@
 use super::{
     manager::fetch_task,
+    process::ProcessControlBlock,
     switch::__switch,
-    task::{TaskControlBlock, TaskStatus},
+    task::{trap_ctx_va, TaskControlBlock, TaskStatus},
+};
+use crate::{
+    fs::poll_stdin,
+    timer::{check_timers, set_next_trigger},
+    trap::TrapContext,
 };
-use crate::{timer::set_next_trigger, trap::TrapContext};
 use alloc::sync::Arc;
 use core::cell::RefCell;
 use lazy_static::lazy_static;
@@ -56,6 +61,10 @@ impl Processor {
                 unsafe {
                     __switch(idle_task_ctx_ptr2, next_task_ctx_ptr2);
                 }
+            } else {
+                // timer interrupts are off in the kernel, so blocked tasks are woken up here
+                check_timers();
+                poll_stdin();
             }
         }
     }
@@ -77,17 +86,23 @@ pub fn current_task() -> Option<Arc<TaskControlBlock>> {
     PROCESSOR.current()
 }
 
+pub fn current_process() -> Arc<ProcessControlBlock> {
+    current_task().unwrap().process.upgrade().unwrap()
+}
+
 pub fn current_user_token() -> usize {
-    current_task()
-        .unwrap()
-        .acquire_inner_lock()
-        .get_user_token()
+    current_process().acquire_inner_lock().get_user_token()
 }
 
 pub fn current_trap_ctx() -> &'static mut TrapContext {
     current_task().unwrap().acquire_inner_lock().get_trap_ctx()
 }
 
+/// Address of the trap context of the current thread in user space.
+pub fn current_trap_ctx_va() -> usize {
+    trap_ctx_va(current_task().unwrap().acquire_inner_lock().tid)
+}
+
 pub fn schedule(switched_task_ctx_ptr2: *const usize) {
     let idle_task_ctx_ptr2 = PROCESSOR.get_idle_task_ctx_ptr2();
     unsafe { __switch(switched_task_ctx_ptr2, idle_task_ctx_ptr2) }
@@ -0,0 +1,237 @@
+use super::task::TaskControlBlockInner;
+
+pub const MAX_SIG: usize = 31;
+/// Handlers which take the default action, or ignore the signal.
+pub const SIG_DFL: usize = 0;
+pub const SIG_IGN: usize = 1;
+
+bitflags! {
+    /// Bit `n` stands for signal `n`, as in Linux.
+    pub struct SignalFlags: u32 {
+        const SIGHUP = 1 << 1;
+        const SIGINT = 1 << 2;
+        const SIGQUIT = 1 << 3;
+        const SIGILL = 1 << 4;
+        const SIGTRAP = 1 << 5;
+        const SIGABRT = 1 << 6;
+        const SIGBUS = 1 << 7;
+        const SIGFPE = 1 << 8;
+        const SIGKILL = 1 << 9;
+        const SIGUSR1 = 1 << 10;
+        const SIGSEGV = 1 << 11;
+        const SIGUSR2 = 1 << 12;
+        const SIGPIPE = 1 << 13;
+        const SIGALRM = 1 << 14;
+        const SIGTERM = 1 << 15;
+        const SIGSTKFLT = 1 << 16;
+        const SIGCHLD = 1 << 17;
+        const SIGCONT = 1 << 18;
+        const SIGSTOP = 1 << 19;
+        const SIGTSTP = 1 << 20;
+        const SIGTTIN = 1 << 21;
+        const SIGTTOU = 1 << 22;
+        const SIGURG = 1 << 23;
+        const SIGXCPU = 1 << 24;
+        const SIGXFSZ = 1 << 25;
+        const SIGVTALRM = 1 << 26;
+        const SIGPROF = 1 << 27;
+        const SIGWINCH = 1 << 28;
+        const SIGIO = 1 << 29;
+        const SIGPWR = 1 << 30;
+        const SIGSYS = 1 << 31;
+    }
+}
+
+impl SignalFlags {
+    pub fn from_signum(signum: usize) -> Option<Self> {
+        if (1..=MAX_SIG).contains(&signum) {
+            Some(Self::from_bits_truncate(1 << signum))
+        } else {
+            None
+        }
+    }
+
+    /// Return the signal of the lowest number in the set.
+    pub fn first(&self) -> Option<usize> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self.bits.trailing_zeros() as usize)
+        }
+    }
+
+    /// Signals which can be neither caught, blocked nor ignored.
+    pub fn unmaskable() -> Self {
+        Self::SIGKILL | Self::SIGSTOP
+    }
+
+    fn stop() -> Self {
+        Self::SIGSTOP | Self::SIGTSTP | Self::SIGTTIN | Self::SIGTTOU
+    }
+}
+
+#[derive(PartialEq)]
+pub enum DefaultAction {
+    Terminate,
+    Ignore,
+    Stop,
+    Continue,
+}
+
+impl DefaultAction {
+    pub fn of(signal: SignalFlags) -> Self {
+        if SignalFlags::stop().contains(signal) {
+            Self::Stop
+        } else if signal == SignalFlags::SIGCONT {
+            Self::Continue
+        } else if (SignalFlags::SIGCHLD | SignalFlags::SIGURG | SignalFlags::SIGWINCH)
+            .contains(signal)
+        {
+            Self::Ignore
+        } else {
+            Self::Terminate
+        }
+    }
+}
+
+/// `restorer` is where the handler returns to, which should call sigreturn.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SignalAction {
+    pub handler: usize,
+    /// Signals blocked in addition while the handler is running.
+    pub mask: SignalFlags,
+    pub restorer: usize,
+}
+
+impl Default for SignalAction {
+    fn default() -> Self {
+        Self {
+            handler: SIG_DFL,
+            mask: SignalFlags::empty(),
+            restorer: 0,
+        }
+    }
+}
+
+/// Signals of a process, which are delivered to any thread not blocking them, while
+/// the mask and the running handler are those of each thread.
+#[derive(Clone)]
+pub struct Signals {
+    /// Signals sent but not delivered yet.
+    pub pending: SignalFlags,
+    pub actions: [SignalAction; MAX_SIG + 1],
+    /// Stopped tasks do not return to user mode until SIGCONT.
+    pub stopped: bool,
+}
+
+impl Signals {
+    pub fn new() -> Self {
+        Self {
+            pending: SignalFlags::empty(),
+            actions: [SignalAction::default(); MAX_SIG + 1],
+            stopped: false,
+        }
+    }
+
+    /// A child inherits actions but no pending signals.
+    pub fn fork(&self) -> Self {
+        Self {
+            pending: SignalFlags::empty(),
+            stopped: false,
+            ..self.clone()
+        }
+    }
+
+    /// Handlers are gone with the old program, while ignored signals stay ignored.
+    pub fn exec(&mut self) {
+        for action in self.actions.iter_mut() {
+            if action.handler != SIG_IGN {
+                *action = SignalAction::default();
+            }
+        }
+    }
+
+    /// Make a signal pending, unless it is ignored.
+    pub fn send(&mut self, signal: SignalFlags) {
+        // stop and continue signals cancel each other
+        if signal == SignalFlags::SIGCONT {
+            self.pending.remove(SignalFlags::stop());
+            self.stopped = false;
+        } else if SignalFlags::stop().contains(signal) {
+            self.pending.remove(SignalFlags::SIGCONT);
+        }
+        if !self.ignored(signal) {
+            self.pending.insert(signal);
+        }
+    }
+
+    /// Deliver a signal caused by thread `task` itself, whose handler is reset to the default
+    /// if the signal cannot be handled right now.
+    pub fn force(&mut self, signal: SignalFlags, task: &mut TaskControlBlockInner) {
+        let signum = signal.first().unwrap();
+        if task.signal_mask.contains(signal)
+            || task.signal_frame.is_some()
+            || self.actions[signum].handler == SIG_IGN
+        {
+            task.signal_mask.remove(signal);
+            self.actions[signum] = SignalAction::default();
+        }
+        self.pending.insert(signal);
+    }
+
+    pub fn ignored(&self, signal: SignalFlags) -> bool {
+        match self.actions[signal.first().unwrap()].handler {
+            SIG_IGN => !SignalFlags::unmaskable().contains(signal),
+            SIG_DFL => DefaultAction::of(signal) == DefaultAction::Ignore,
+            _ => false,
+        }
+    }
+
+    /// Signals which can be delivered to thread `task` now, which interrupt its blocking
+    /// syscalls. Signals with handlers stay pending while another handler is running.
+    pub fn deliverable(&self, task: &TaskControlBlockInner) -> SignalFlags {
+        let mut blocked = task.signal_mask;
+        if task.signal_frame.is_some() {
+            blocked |= self.handled();
+        }
+        self.pending & !(blocked - SignalFlags::unmaskable())
+    }
+
+    /// Signals with handlers.
+    fn handled(&self) -> SignalFlags {
+        (1..=MAX_SIG)
+            .filter(|&signum| self.actions[signum].handler > SIG_IGN)
+            .filter_map(SignalFlags::from_signum)
+            .fold(SignalFlags::empty(), |handled, signal| handled | signal)
+    }
+
+    /// Whether a deliverable signal terminates the process, which should stop blocking.
+    pub fn fatal(&self, task: &TaskControlBlockInner) -> bool {
+        let mut deliverable = self.deliverable(task);
+        while let Some(signum) = deliverable.first() {
+            let signal = SignalFlags::from_signum(signum).unwrap();
+            deliverable.remove(signal);
+            if SignalFlags::unmaskable().contains(signal)
+                || self.actions[signum].handler == SIG_DFL
+                    && DefaultAction::of(signal) == DefaultAction::Terminate
+            {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Take the next signal to deliver and its action, see `deliverable`.
+    pub fn next(&mut self, task: &TaskControlBlockInner) -> Option<(usize, SignalAction)> {
+        let signum = self.deliverable(task).first()?;
+        let signal = SignalFlags::from_signum(signum).unwrap();
+        let action = if SignalFlags::unmaskable().contains(signal) {
+            SignalAction::default()
+        } else {
+            self.actions[signum]
+        };
+        self.pending.remove(signal);
+        Some((signum, action))
+    }
+}
@@ -0,0 +1,41 @@
+use super::{current_task, wake_up_task, TaskControlBlock};
+use alloc::{
+    collections::VecDeque,
+    sync::{Arc, Weak},
+};
+
+/// Tasks blocked until an event, guarded by the lock of whatever the event is about.
+///
+/// A task puts itself in the queue with the lock held, then releases the lock and calls
+/// `block_current_and_run_next`. It should check its condition again after woken up,
+/// as wakeups may be spurious.
+#[derive(Default)]
+pub struct WaitQueue {
+    /// Blocked tasks are kept alive by their processes.
+    tasks: VecDeque<Weak<TaskControlBlock>>,
+}
+
+impl WaitQueue {
+    pub fn new() -> Self {
+        Self {
+            tasks: VecDeque::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    pub fn add_current(&mut self) {
+        self.tasks
+            .push_back(current_task().as_ref().map(Arc::downgrade).unwrap());
+    }
+
+    pub fn wake_all(&mut self) {
+        for task in self.tasks.drain(..) {
+            if let Some(task) = task.upgrade() {
+                wake_up_task(task);
+            }
+        }
+    }
+}
@@ -1,6 +1,11 @@
 use crate::config::CLOCK_FREQ;
 use crate::sbi;
+use crate::task::{wake_up_task, TaskControlBlock};
+use alloc::{collections::BinaryHeap, sync::Arc};
+use core::cmp::Ordering;
+use lazy_static::lazy_static;
 use riscv::register::time;
+use spin::Mutex;
 
 const TICKS_PER_SEC: usize = 200;
 pub const USEC_PER_SEC: usize = 1000000;
@@ -16,3 +21,58 @@ pub fn get_time_us() -> usize {
 pub fn set_next_trigger() {
     sbi::set_timer(get_time() + CLOCK_FREQ / TICKS_PER_SEC);
 }
+
+/// A task sleeping until `deadline` in microseconds.
+struct Timer {
+    deadline: usize,
+    task: Arc<TaskControlBlock>,
+}
+
+impl PartialEq for Timer {
+    fn eq(&self, other: &Self) -> bool {
+        self.deadline.eq(&other.deadline)
+    }
+}
+impl Eq for Timer {}
+impl PartialOrd for Timer {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Timer {
+    /// The earliest deadline comes first in the heap.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.deadline.cmp(&other.deadline).reverse()
+    }
+}
+
+lazy_static! {
+    static ref TIMERS: Mutex<BinaryHeap<Timer>> = Mutex::new(BinaryHeap::new());
+}
+
+/// Wake up `task` at `deadline` in microseconds, which keeps it alive until then.
+pub fn add_timer(deadline: usize, task: Arc<TaskControlBlock>) {
+    TIMERS.lock().push(Timer { deadline, task });
+}
+
+/// Remove timers of `task`, which is woken up before them.
+pub fn remove_timers(task: &Arc<TaskControlBlock>) {
+    let mut timers = TIMERS.lock();
+    let rest = timers
+        .drain()
+        .filter(|timer| !Arc::ptr_eq(&timer.task, task))
+        .collect();
+    *timers = rest;
+}
+
+/// Wake up tasks whose deadlines have passed.
+pub fn check_timers() {
+    let now = get_time_us();
+    let mut timers = TIMERS.lock();
+    while let Some(timer) = timers.peek() {
+        if timer.deadline > now {
+            break;
+        }
+        wake_up_task(timers.pop().unwrap().task);
+    }
+}
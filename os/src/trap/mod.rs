@@ -1,12 +1,15 @@
 mod context;
 
 use crate::{
-    config::{TRAMPOLINE, TRAP_CONTEXT},
+    config::TRAMPOLINE,
+    fs::poll_stdin,
+    mm::{AccessType, VirtAddr},
     syscall::syscall,
     task::{
-        current_trap_ctx, current_user_token, exit_current_and_run_next,
-        suspend_current_and_run_next,
+        current_force_signal, current_handle_page_fault, current_trap_ctx, current_trap_ctx_va,
+        current_user_token, handle_signals, suspend_current_and_run_next, SignalFlags,
     },
+    timer::check_timers,
 };
 pub use context::TrapContext;
 use riscv::register::{
@@ -64,6 +67,8 @@ fn trap_handler() -> ! {
     let stval = stval::read();
     match scause.cause() {
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
+            check_timers();
+            poll_stdin();
             suspend_current_and_run_next();
         }
         Trap::Exception(Exception::UserEnvCall) => {
@@ -71,23 +76,47 @@ fn trap_handler() -> ! {
             // jump to next instruction anyway
             ctx.sepc += 4;
             // get system call return value
-            let result = syscall(ctx.x[17], [ctx.x[10], ctx.x[11], ctx.x[12]]);
+            let result = syscall(
+                ctx.x[17],
+                [
+                    ctx.x[10], ctx.x[11], ctx.x[12], ctx.x[13], ctx.x[14], ctx.x[15],
+                ],
+            );
             // ctx is changed during sys_exec, so we have to call it again
             ctx = current_trap_ctx();
             ctx.x[10] = result as usize;
         }
+        Trap::Exception(Exception::LoadPageFault)
+        | Trap::Exception(Exception::StorePageFault)
+        | Trap::Exception(Exception::InstructionPageFault) => {
+            let access = match scause.cause() {
+                Trap::Exception(Exception::LoadPageFault) => AccessType::Read,
+                Trap::Exception(Exception::StorePageFault) => AccessType::Write,
+                _ => AccessType::Execute,
+            };
+            if !current_handle_page_fault(VirtAddr::from(stval).floor(), access) {
+                warn!(
+                    "Segmentation fault in application: {:?} at {:#x}, sepc = {:#x}, core dumped.",
+                    access,
+                    stval,
+                    current_trap_ctx().sepc
+                );
+                current_force_signal(SignalFlags::SIGSEGV);
+            }
+        }
         Trap::Exception(Exception::LoadFault)
-        | Trap::Exception(Exception::LoadPageFault)
         | Trap::Exception(Exception::StoreFault)
-        | Trap::Exception(Exception::StorePageFault) => {
-            warn!("PageFault in application, core dumped.");
-            // page fault exit code
-            exit_current_and_run_next(-2);
+        | Trap::Exception(Exception::InstructionFault) => {
+            warn!(
+                "{:?} in application at {:#x}, core dumped.",
+                scause.cause(),
+                stval
+            );
+            current_force_signal(SignalFlags::SIGSEGV);
         }
         Trap::Exception(Exception::IllegalInstruction) => {
             warn!("IllegalInstruction in application, core dumped.");
-            // illegal instruction exit code
-            exit_current_and_run_next(-3);
+            current_force_signal(SignalFlags::SIGILL);
         }
         _ => {
             panic!(
@@ -97,12 +126,13 @@ fn trap_handler() -> ! {
             );
         }
     }
+    handle_signals();
     trap_return();
 }
 
 pub fn trap_return() -> ! {
     set_user_trap_entry();
-    let trap_ctx_ptr = TRAP_CONTEXT;
+    let trap_ctx_ptr = current_trap_ctx_va();
     let user_satp = current_user_token();
     extern "C" {
         fn __alltraps();
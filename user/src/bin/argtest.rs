@@ -0,0 +1,108 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exec, fork, open, spawn, unlink, waitpid, write, OpenFlags};
+
+const ENOEXEC: isize = 8;
+const EACCES: isize = 13;
+
+const ARGS: &[&str] = &["argtest\0", "hello\0", "\0", "world\0"];
+
+const EHDR_SIZE: usize = 64;
+const PHDR_SIZE: usize = 56;
+const CODE_START: usize = EHDR_SIZE + 2 * PHDR_SIZE;
+/// li a0, 7; li a7, 93 (exit); ecall
+const CODE: [u32; 3] = [0x0070_0513, 0x05d0_0893, 0x0000_0073];
+const ELF_SIZE: usize = CODE_START + 4 * CODE.len();
+const LOAD_ADDR: usize = 0x10000;
+
+fn put(elf: &mut [u8], offset: usize, bytes: &[u8]) {
+    elf[offset..offset + bytes.len()].copy_from_slice(bytes);
+}
+
+/// An executable which exits with 7, with an empty segment after the one of its code.
+fn tiny_elf() -> [u8; ELF_SIZE] {
+    let mut elf = [0u8; ELF_SIZE];
+    put(&mut elf, 0, &[0x7f, b'E', b'L', b'F', 2, 1, 1]);
+    put(&mut elf, 16, &2u16.to_le_bytes());
+    put(&mut elf, 18, &0xf3u16.to_le_bytes());
+    put(&mut elf, 20, &1u32.to_le_bytes());
+    put(
+        &mut elf,
+        24,
+        &((LOAD_ADDR + CODE_START) as u64).to_le_bytes(),
+    );
+    put(&mut elf, 32, &(EHDR_SIZE as u64).to_le_bytes());
+    put(&mut elf, 52, &(EHDR_SIZE as u16).to_le_bytes());
+    put(&mut elf, 54, &(PHDR_SIZE as u16).to_le_bytes());
+    put(&mut elf, 56, &2u16.to_le_bytes());
+    put(&mut elf, 58, &64u16.to_le_bytes());
+    // PT_LOAD of the whole file, readable and executable, then an empty PT_LOAD
+    for (i, &(addr, size)) in [(LOAD_ADDR, ELF_SIZE), (LOAD_ADDR * 2, 0)]
+        .iter()
+        .enumerate()
+    {
+        let phdr = EHDR_SIZE + i * PHDR_SIZE;
+        put(&mut elf, phdr, &1u32.to_le_bytes());
+        put(&mut elf, phdr + 4, &5u32.to_le_bytes());
+        put(&mut elf, phdr + 16, &(addr as u64).to_le_bytes());
+        put(&mut elf, phdr + 24, &(addr as u64).to_le_bytes());
+        put(&mut elf, phdr + 32, &(size as u64).to_le_bytes());
+        put(&mut elf, phdr + 40, &(size as u64).to_le_bytes());
+        put(&mut elf, phdr + 48, &4096u64.to_le_bytes());
+    }
+    for (i, inst) in CODE.iter().enumerate() {
+        put(&mut elf, CODE_START + 4 * i, &inst.to_le_bytes());
+    }
+    elf
+}
+
+/// Run itself with `ARGS`, both by fork+exec and by spawn, the child checks what it gets.
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc > 1 {
+        assert_eq!(argc, ARGS.len());
+        assert_eq!(argv.len(), argc);
+        for (arg, expected) in argv.iter().zip(ARGS) {
+            assert_eq!(*arg, expected.trim_end_matches('\0'));
+        }
+        return 0;
+    }
+    let pid = fork();
+    if pid == 0 {
+        exec("argtest\0", ARGS);
+        panic!("unreachable!");
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    let pid = spawn("argtest\0", ARGS);
+    assert!(pid > 0);
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    // directories and files other than executables cannot be run
+    assert_eq!(exec("/\0", ARGS), -EACCES);
+    assert_eq!(spawn("/\0", ARGS), -EACCES);
+    let fd = open("notelf\0", OpenFlags::CREAT | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, b"#!/bin/sh\n"), 10);
+    close(fd as usize);
+    assert_eq!(exec("notelf\0", ARGS), -ENOEXEC);
+    assert_eq!(spawn("notelf\0", ARGS), -ENOEXEC);
+    assert_eq!(unlink("notelf\0"), 0);
+    // empty segments are skipped
+    let fd = open("tinyelf\0", OpenFlags::CREAT | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, &tiny_elf()), ELF_SIZE as isize);
+    close(fd as usize);
+    let pid = spawn("tinyelf\0", ARGS);
+    assert!(pid > 0);
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    assert_eq!(exit_code, 7);
+    assert_eq!(unlink("tinyelf\0"), 0);
+    println!("argtest passed!");
+    0
+}
@@ -0,0 +1,47 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::{brk, sbrk};
+
+const PAGE_SIZE: usize = 4096;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let bottom = sbrk(0);
+    assert!(bottom > 0);
+    // grow the heap and touch it
+    assert_eq!(sbrk((PAGE_SIZE * 3) as isize), bottom);
+    let heap = unsafe { core::slice::from_raw_parts_mut(bottom as *mut u8, PAGE_SIZE * 3) };
+    heap[0] = 1;
+    heap[PAGE_SIZE * 3 - 1] = 2;
+    // pages freed by shrinking read as zero after growing again
+    assert_eq!(
+        sbrk(-((PAGE_SIZE * 2) as isize)),
+        bottom + (PAGE_SIZE * 3) as isize
+    );
+    assert_eq!(sbrk((PAGE_SIZE * 2) as isize), bottom + PAGE_SIZE as isize);
+    assert_eq!(heap[0], 1);
+    assert_eq!(heap[PAGE_SIZE * 3 - 1], 0);
+    // the program break cannot move below the heap or into the stack
+    let end = bottom + (PAGE_SIZE * 3) as isize;
+    assert_eq!(brk(0), end);
+    assert_eq!(brk(bottom as usize - 1), end);
+    assert_eq!(brk(usize::MAX / 2), end);
+    assert_eq!(brk(bottom as usize), bottom);
+    // the allocator extends the heap by itself
+    let mut v: Vec<usize> = Vec::new();
+    for i in 0..64 * 1024 {
+        v.push(i);
+    }
+    for (i, &value) in v.iter().enumerate() {
+        assert_eq!(value, i);
+    }
+    assert!(sbrk(0) > bottom);
+    println!("brktest passed!");
+    0
+}
@@ -0,0 +1,19 @@
+#![no_std]
+#![no_main]
+
+extern crate user_lib;
+
+use user_lib::{fstat, Stat};
+
+/// Exec'ed by duptest, with fd 10 inherited and fd 11 closed on exec.
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut stat = Stat::new();
+    if fstat(10, &mut stat) != 0 {
+        return 1;
+    }
+    if fstat(11, &mut stat) == 0 {
+        return 2;
+    }
+    0
+}
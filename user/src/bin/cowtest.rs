@@ -0,0 +1,73 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exit, fork, pipe, read, waitpid, write, yield_};
+
+const PAGES: usize = 16;
+const PAGE_SIZE: usize = 4096;
+
+static mut DATA: [u8; PAGES * PAGE_SIZE] = [0; PAGES * PAGE_SIZE];
+
+fn wait_child(pid: isize) {
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+}
+
+/// Pages written after fork, by the program or by the kernel, are private to each process.
+#[no_mangle]
+pub fn main() -> i32 {
+    let data = unsafe { &mut DATA };
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte = (i / PAGE_SIZE) as u8;
+    }
+    let pid = fork();
+    if pid == 0 {
+        // what the parent wrote is shared
+        for (i, byte) in data.iter().enumerate() {
+            assert_eq!(*byte, (i / PAGE_SIZE) as u8);
+        }
+        // write to every other page
+        for page in data.chunks_mut(PAGE_SIZE).step_by(2) {
+            page.iter_mut().for_each(|byte| *byte = 0xff);
+        }
+        exit(0);
+    }
+    wait_child(pid);
+    for (i, byte) in data.iter().enumerate() {
+        assert_eq!(*byte, (i / PAGE_SIZE) as u8);
+    }
+    // the kernel writes to a shared page in read()
+    let mut pipe_fd = [0usize; 2];
+    pipe(&mut pipe_fd);
+    let pid = fork();
+    if pid == 0 {
+        close(pipe_fd[1]);
+        let page = &mut data[PAGE_SIZE..PAGE_SIZE * 2];
+        assert_eq!(read(pipe_fd[0], &mut page[..5]), 5);
+        assert_eq!(&page[..5], b"hello");
+        assert!(page[5..].iter().all(|byte| *byte == 1));
+        exit(0);
+    }
+    close(pipe_fd[0]);
+    assert_eq!(write(pipe_fd[1], b"hello"), 5);
+    close(pipe_fd[1]);
+    wait_child(pid);
+    assert!(data[PAGE_SIZE..PAGE_SIZE * 2].iter().all(|byte| *byte == 1));
+    // the parent writes after fork
+    let pid = fork();
+    if pid == 0 {
+        for _ in 0..10 {
+            yield_();
+        }
+        assert!(data.iter().all(|byte| *byte != 0xee));
+        exit(0);
+    }
+    data.iter_mut().for_each(|byte| *byte = 0xee);
+    wait_child(pid);
+    println!("cowtest passed!");
+    0
+}
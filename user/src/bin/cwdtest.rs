@@ -0,0 +1,60 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    chdir, close, exec, exit, fork, getcwd, mkdir, open, rmdir, unlink, waitpid, OpenFlags,
+};
+
+fn cwd_is(expected: &str) -> bool {
+    let mut buffer = [0u8; 64];
+    let len = getcwd(&mut buffer);
+    len as usize == expected.len() + 1 && &buffer[..expected.len()] == expected.as_bytes()
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(chdir("/\0"), 0);
+    assert!(cwd_is("/"));
+    assert_eq!(mkdir("cwdtest\0"), 0);
+    assert_eq!(mkdir("cwdtest/sub\0"), 0);
+    assert_eq!(chdir("cwdtest/./sub\0"), 0);
+    assert!(cwd_is("/cwdtest/sub"));
+    assert_eq!(chdir("..\0"), 0);
+    assert!(cwd_is("/cwdtest"));
+    assert!(chdir("nonexistent\0") < 0);
+    assert!(cwd_is("/cwdtest"));
+
+    // relative paths start from cwd
+    let fd = open("sub/../file\0", OpenFlags::CREAT | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    let fd = open("/cwdtest/file\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    assert!(chdir("file\0") < 0);
+    let mut buffer = [0u8; 4];
+    assert!(getcwd(&mut buffer) < 0);
+
+    // cwd is inherited by the child, and applications are found from it
+    let pid = fork();
+    if pid == 0 {
+        if !cwd_is("/cwdtest") {
+            exit(-1);
+        }
+        exec("../hello_world\0", &["hello_world\0"]);
+        exit(-2);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+
+    assert_eq!(unlink("file\0"), 0);
+    assert_eq!(rmdir("sub\0"), 0);
+    assert_eq!(chdir("/\0"), 0);
+    assert_eq!(rmdir("cwdtest\0"), 0);
+    println!("cwdtest passed!");
+    0
+}
@@ -0,0 +1,95 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, getdents, link, mkdir, open, openat, parse_dirents, read, rmdir, unlink, write,
+    OpenFlags, DT_DIR, DT_REG,
+};
+
+const EFAULT: isize = 14;
+
+fn names_in(path: &str) -> usize {
+    let fd = open(path, OpenFlags::RDONLY | OpenFlags::DIRECTORY);
+    assert!(fd > 0);
+    // entries are not consumed if they cannot be copied
+    let bad_buffer = unsafe { core::slice::from_raw_parts_mut(8 as *mut u8, 64) };
+    assert_eq!(getdents(fd as usize, bad_buffer), -EFAULT);
+    let mut buffer = [0u8; 64];
+    let mut count = 0;
+    loop {
+        let len = getdents(fd as usize, &mut buffer);
+        assert!(len >= 0);
+        if len == 0 {
+            break;
+        }
+        for dirent in parse_dirents(&buffer[..len as usize]) {
+            match dirent.name {
+                "." | ".." | "sub" => assert_eq!(dirent.type_, DT_DIR),
+                _ => assert_eq!(dirent.type_, DT_REG),
+            }
+            count += 1;
+        }
+    }
+    close(fd as usize);
+    count
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let test_str = "Hello, directories!";
+    assert_eq!(mkdir("/dirtest\0"), 0);
+    assert!(mkdir("/dirtest\0") < 0);
+    assert_eq!(mkdir("/dirtest/sub\0"), 0);
+    assert!(mkdir("/nonexistent/sub\0") < 0);
+
+    // create a file relative to a directory fd
+    let dirfd = open("/dirtest/sub/..\0", OpenFlags::RDONLY);
+    assert!(dirfd > 0);
+    let fd = openat(dirfd, "a\0", OpenFlags::CREAT | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    write(fd as usize, test_str.as_bytes());
+    close(fd as usize);
+    close(dirfd as usize);
+    assert!(open("/dirtest\0", OpenFlags::WRONLY) < 0);
+    assert!(open("/dirtest/a/b\0", OpenFlags::RDONLY) < 0);
+
+    // the content is kept by the other link
+    assert_eq!(link("/dirtest/a\0", "/dirtest/sub/b\0"), 0);
+    assert!(link("/dirtest/a\0", "/dirtest/sub/b\0") < 0);
+    assert!(link("/dirtest/sub\0", "/dirtest/c\0") < 0);
+    assert_eq!(names_in("/dirtest\0"), 4);
+    assert_eq!(unlink("/dirtest/a\0"), 0);
+    assert!(unlink("/dirtest/a\0") < 0);
+    assert_eq!(names_in("/dirtest\0"), 3);
+    let fd = open("/dirtest/sub/b\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buffer = [0u8; 100];
+    let read_len = read(fd as usize, &mut buffer) as usize;
+    close(fd as usize);
+    assert_eq!(test_str, core::str::from_utf8(&buffer[..read_len]).unwrap());
+
+    // only empty directories can be removed
+    assert!(unlink("/dirtest/sub\0") < 0);
+    assert!(rmdir("/dirtest/sub\0") < 0);
+    assert!(rmdir("/dirtest/sub/b\0") < 0);
+    // an open file stays readable without links, and its inode is not reused meanwhile
+    let fd = open("/dirtest/sub/b\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    assert_eq!(unlink("/dirtest/sub/b\0"), 0);
+    let other = open("/dirtest/c\0", OpenFlags::CREAT | OpenFlags::WRONLY);
+    assert!(other > 0);
+    write(other as usize, b"Written to another file");
+    close(other as usize);
+    assert_eq!(unlink("/dirtest/c\0"), 0);
+    let read_len = read(fd as usize, &mut buffer) as usize;
+    close(fd as usize);
+    assert_eq!(test_str, core::str::from_utf8(&buffer[..read_len]).unwrap());
+    assert_eq!(rmdir("/dirtest/sub\0"), 0);
+    assert_eq!(rmdir("/dirtest\0"), 0);
+    assert!(open("/dirtest\0", OpenFlags::RDONLY) < 0);
+    println!("dirtest passed!");
+    0
+}
@@ -0,0 +1,80 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, dup, dup3, exec, exit, fork, open, pipe, read, unlink, waitpid, write, OpenFlags,
+};
+
+const EBADF: isize = 9;
+const EMFILE: isize = 24;
+const MAX_FD_NUM: usize = 256;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open(
+        "dup_a\0",
+        OpenFlags::CREAT | OpenFlags::WRONLY | OpenFlags::TRUNC,
+    );
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let new_fd = dup(fd);
+    assert!(new_fd > 0 && new_fd as usize != fd);
+    let new_fd = new_fd as usize;
+    // the offset is shared between duplicated fds
+    write(fd, b"ab");
+    write(new_fd, b"cd");
+    close(new_fd);
+    assert!(dup(new_fd) < 0);
+    assert!(dup3(fd, fd, OpenFlags::empty()) < 0);
+    assert!(dup3(fd, 10, OpenFlags::APPEND) < 0);
+
+    // redirect stdout of the child
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(dup3(fd, 1, OpenFlags::empty()), 1);
+        println!("ef");
+        exit(0);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    close(fd);
+    let fd = open("dup_a\0", OpenFlags::RDONLY) as usize;
+    let mut buffer = [0u8; 16];
+    let len = read(fd, &mut buffer) as usize;
+    assert_eq!(&buffer[..len], b"abcdef\n");
+
+    // only fds without close-on-exec survive exec
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(dup3(fd, 10, OpenFlags::empty()), 10);
+        assert_eq!(dup3(fd, 11, OpenFlags::CLOEXEC), 11);
+        exec("cloexec_child\0", &["cloexec_child\0"]);
+        exit(-1);
+    }
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    close(fd);
+    unlink("dup_a\0");
+    // no more fds than the limit
+    let mut last_fd = 0;
+    loop {
+        let new_fd = dup(0);
+        if new_fd < 0 {
+            assert_eq!(new_fd, -EMFILE);
+            break;
+        }
+        last_fd = new_fd as usize;
+    }
+    assert_eq!(last_fd, MAX_FD_NUM - 1);
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), -EMFILE);
+    assert_eq!(dup3(0, MAX_FD_NUM, OpenFlags::RDONLY), -EBADF);
+    for fd in 3..MAX_FD_NUM {
+        close(fd);
+    }
+    println!("duptest passed!");
+    0
+}
@@ -0,0 +1,67 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    environ, exec, execve, exit, fork, getauxval, getenv, waitpid, AT_ENTRY, AT_PAGESZ, AT_PHDR,
+    AT_PHENT, AT_PHNUM, AT_RANDOM,
+};
+
+const ENVS: &[&str] = &["FOO=bar\0", "EMPTY=\0", "PATH=/bin:/\0"];
+
+const PT_LOAD: u32 = 1;
+
+/// Check the environment given by `ENVS` and the auxiliary vector.
+fn check() {
+    assert_eq!(environ().len(), ENVS.len());
+    assert_eq!(getenv("FOO"), Some("bar"));
+    assert_eq!(getenv("EMPTY"), Some(""));
+    assert_eq!(getenv("PATH"), Some("/bin:/"));
+    assert_eq!(getenv("FO"), None);
+    assert_eq!(getenv("HOME"), None);
+    assert_eq!(getauxval(AT_PAGESZ), 4096);
+    assert_eq!(getauxval(AT_PHENT), 56);
+    assert_ne!(getauxval(AT_RANDOM), 0);
+    // the entry point lies in one of the loadable segments
+    let entry = getauxval(AT_ENTRY) as u64;
+    let phdr = getauxval(AT_PHDR);
+    let found = (0..getauxval(AT_PHNUM)).any(|i| unsafe {
+        let ph = (phdr + i * 56) as *const u8;
+        let p_type = *(ph as *const u32);
+        let p_vaddr = *(ph.add(16) as *const u64);
+        let p_memsz = *(ph.add(40) as *const u64);
+        p_type == PT_LOAD && p_vaddr <= entry && entry < p_vaddr + p_memsz
+    });
+    assert!(found);
+}
+
+fn run(args: &[&str]) {
+    let pid = fork();
+    if pid == 0 {
+        execve("envtest\0", args, ENVS);
+        panic!("unreachable!");
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+}
+
+#[no_mangle]
+pub fn main(_argc: usize, argv: &[&str]) -> i32 {
+    match argv {
+        [_, "check"] => check(),
+        [_, "inherit"] => {
+            // exec passes the environment on
+            exec("envtest\0", &["envtest\0", "check\0"]);
+            exit(-1);
+        }
+        _ => {
+            run(&["envtest\0", "check\0"]);
+            run(&["envtest\0", "inherit\0"]);
+            println!("envtest passed!");
+        }
+    }
+    0
+}
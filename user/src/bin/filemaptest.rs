@@ -0,0 +1,140 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, fstat, mmap, msync, munmap, open, pread, pwrite, unlink, waitpid, write,
+    OpenFlags, Stat, MAP_PRIVATE, MAP_SHARED, MS_SYNC, PROT_READ, PROT_WRITE,
+};
+
+const PAGE_SIZE: usize = 4096;
+const FILE_SIZE: usize = PAGE_SIZE * 2 + 100;
+const EACCES: isize = 13;
+const EBADF: isize = 9;
+const RW: usize = PROT_READ | PROT_WRITE;
+
+fn byte_at(offset: usize) -> u8 {
+    (offset % 251) as u8
+}
+
+fn pages(start: isize, n: usize) -> &'static mut [u8] {
+    assert!(start > 0);
+    unsafe { core::slice::from_raw_parts_mut(start as *mut u8, n * PAGE_SIZE) }
+}
+
+fn file_size(fd: usize) -> usize {
+    let mut stat = Stat::new();
+    assert_eq!(fstat(fd, &mut stat), 0);
+    stat.size as usize
+}
+
+fn read_byte(fd: usize, offset: usize) -> u8 {
+    let mut buffer = [0u8; 1];
+    assert_eq!(pread(fd, &mut buffer, offset), 1);
+    buffer[0]
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open(
+        "filemap_a\0",
+        OpenFlags::CREAT | OpenFlags::RDWR | OpenFlags::TRUNC,
+    );
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let data: [u8; FILE_SIZE] = {
+        let mut data = [0u8; FILE_SIZE];
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = byte_at(i);
+        }
+        data
+    };
+    assert_eq!(write(fd, &data), FILE_SIZE as isize);
+
+    // a private mapping from the second page, with zeros beyond the end of the file
+    let private = mmap(0, PAGE_SIZE * 2, RW, MAP_PRIVATE, fd as isize, PAGE_SIZE);
+    let bytes = pages(private, 2);
+    for (i, &byte) in bytes.iter().enumerate() {
+        let offset = PAGE_SIZE + i;
+        assert_eq!(
+            byte,
+            if offset < FILE_SIZE {
+                byte_at(offset)
+            } else {
+                0
+            }
+        );
+    }
+    bytes[0] = !byte_at(PAGE_SIZE);
+    assert_eq!(
+        munmap(private as usize, PAGE_SIZE * 2),
+        (PAGE_SIZE * 2) as isize
+    );
+    assert_eq!(read_byte(fd, PAGE_SIZE), byte_at(PAGE_SIZE));
+
+    // other mappings of the file see writes to a shared one only if mapped after msync
+    let shared = mmap(0, PAGE_SIZE, RW, MAP_SHARED, fd as isize, 0);
+    let other = mmap(0, PAGE_SIZE, RW, MAP_SHARED, fd as isize, 0);
+    pages(shared, 1)[0] = 4;
+    assert_eq!(pages(other, 1)[0], byte_at(0));
+    assert_eq!(msync(shared as usize, PAGE_SIZE, MS_SYNC), 0);
+    assert_eq!(read_byte(fd, 0), 4);
+    assert_eq!(pages(other, 1)[0], byte_at(0));
+    let later = mmap(0, PAGE_SIZE, PROT_READ, MAP_SHARED, fd as isize, 0);
+    assert_eq!(pages(later, 1)[0], 4);
+    // pages not written since are not written back, keeping other writes to the file
+    assert_eq!(pwrite(fd, &[5], 1), 1);
+    for &start in [shared, other, later].iter() {
+        assert_eq!(munmap(start as usize, PAGE_SIZE), PAGE_SIZE as isize);
+    }
+    assert_eq!(read_byte(fd, 0), 4);
+    assert_eq!(read_byte(fd, 1), 5);
+    assert_eq!(pwrite(fd, &data[..2], 0), 2);
+
+    // a shared mapping stays valid after the fd is closed
+    let shared = mmap(0, PAGE_SIZE * 3, RW, MAP_SHARED, fd as isize, 0);
+    let bytes = pages(shared, 3);
+    assert_eq!(bytes[FILE_SIZE - 1], byte_at(FILE_SIZE - 1));
+    bytes[0] = 1;
+    bytes[FILE_SIZE] = 1;
+    assert_eq!(msync(shared as usize, PAGE_SIZE * 3, MS_SYNC), 0);
+    assert_eq!(read_byte(fd, 0), 1);
+    // the file does not grow
+    assert_eq!(file_size(fd), FILE_SIZE);
+    let reader = open("filemap_a\0", OpenFlags::RDONLY);
+    assert!(reader > 0);
+    assert_eq!(close(fd), 0);
+
+    // writes of a child are seen in the parent, and reach the file on exit
+    let pid = fork();
+    if pid == 0 {
+        pages(shared, 3)[PAGE_SIZE] = 2;
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(bytes[PAGE_SIZE], 2);
+    assert_eq!(read_byte(reader as usize, PAGE_SIZE), 2);
+    // and those of the parent on munmap
+    bytes[PAGE_SIZE * 2] = 3;
+    assert_eq!(
+        munmap(shared as usize, PAGE_SIZE * 3),
+        (PAGE_SIZE * 3) as isize
+    );
+    assert_eq!(read_byte(reader as usize, PAGE_SIZE * 2), 3);
+
+    // a read-only file cannot be shared writable
+    assert_eq!(mmap(0, PAGE_SIZE, RW, MAP_SHARED, reader, 0), -EACCES);
+    let shared = mmap(0, PAGE_SIZE, PROT_READ, MAP_SHARED, reader, 0);
+    assert_eq!(pages(shared, 1)[0], 1);
+    // written back before the file is removed
+    assert_eq!(munmap(shared as usize, PAGE_SIZE), PAGE_SIZE as isize);
+    assert_eq!(close(reader as usize), 0);
+    assert_eq!(mmap(0, PAGE_SIZE, RW, MAP_PRIVATE, reader, 0), -EBADF);
+    assert_eq!(unlink("filemap_a\0"), 0);
+    println!("filemaptest passed!");
+    0
+}
@@ -0,0 +1,47 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, open, read, write, OpenFlags};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let test_str = "Hello, world!";
+    let filea = "filea\0";
+    let fd = open(filea, OpenFlags::CREAT | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    write(fd, test_str.as_bytes());
+    close(fd);
+
+    let fd = open(filea, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut buffer = [0u8; 100];
+    let read_len = read(fd, &mut buffer) as usize;
+    close(fd);
+    assert_eq!(test_str, core::str::from_utf8(&buffer[..read_len]).unwrap());
+
+    // appending keeps the old content
+    let fd = open(filea, OpenFlags::WRONLY | OpenFlags::APPEND) as usize;
+    write(fd, test_str.as_bytes());
+    close(fd);
+    let fd = open(filea, OpenFlags::RDONLY) as usize;
+    assert_eq!(read(fd, &mut buffer) as usize, test_str.len() * 2);
+    close(fd);
+
+    // truncating drops it
+    let fd = open(filea, OpenFlags::WRONLY | OpenFlags::TRUNC) as usize;
+    close(fd);
+    let fd = open(filea, OpenFlags::RDONLY) as usize;
+    assert_eq!(read(fd, &mut buffer), 0);
+    close(fd);
+
+    assert!(open("nonexistent\0", OpenFlags::RDONLY) < 0);
+    assert!(open(filea, OpenFlags::CREAT | OpenFlags::EXCL) < 0);
+    assert!(open(filea, OpenFlags::WRONLY | OpenFlags::RDWR) < 0);
+    println!("file_test passed!");
+    0
+}
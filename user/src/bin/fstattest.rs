@@ -0,0 +1,50 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fstat, link, open, pipe, unlink, write, OpenFlags, Stat, StatMode};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut stat = Stat::new();
+    assert_eq!(fstat(0, &mut stat), 0);
+    assert_eq!(stat.mode, StatMode::CHR);
+    assert_eq!(fstat(1, &mut stat), 0);
+    assert_eq!(stat.mode, StatMode::CHR);
+    assert!(fstat(100, &mut stat) < 0);
+
+    let mut pipe_fd = [0usize; 2];
+    pipe(&mut pipe_fd);
+    assert_eq!(fstat(pipe_fd[0], &mut stat), 0);
+    assert_eq!(stat.mode, StatMode::FIFO);
+    close(pipe_fd[0]);
+    close(pipe_fd[1]);
+
+    let fd = open("/\0", OpenFlags::RDONLY);
+    assert_eq!(fstat(fd as usize, &mut stat), 0);
+    assert_eq!(stat.mode, StatMode::DIR);
+    close(fd as usize);
+
+    let test_str = "Hello, fstat!";
+    let fd = open("fstat_a\0", OpenFlags::CREAT | OpenFlags::WRONLY) as usize;
+    write(fd, test_str.as_bytes());
+    assert_eq!(fstat(fd, &mut stat), 0);
+    assert_eq!(stat.mode, StatMode::FILE);
+    assert_eq!(stat.size, test_str.len() as u64);
+    assert_eq!(stat.nlink, 1);
+    let ino = stat.ino;
+    assert_eq!(link("fstat_a\0", "fstat_b\0"), 0);
+    assert_eq!(fstat(fd, &mut stat), 0);
+    assert_eq!(stat.nlink, 2);
+    close(fd);
+    let fd = open("fstat_b\0", OpenFlags::RDONLY) as usize;
+    assert_eq!(fstat(fd, &mut stat), 0);
+    assert_eq!(stat.ino, ino);
+    close(fd);
+    unlink("fstat_a\0");
+    unlink("fstat_b\0");
+    println!("fstattest passed!");
+    0
+}
@@ -4,13 +4,13 @@
 #[macro_use]
 extern crate user_lib;
 
-use user_lib::{exec, exit, fork, wait};
+use user_lib::{execve, exit, fork, wait};
 
 #[no_mangle]
 fn main() {
     println!("[initproc] Hello!");
     if fork() == 0 {
-        exec("user_shell\0");
+        execve("user_shell\0", &["user_shell\0"], &["HOME=/\0"]);
     } else {
         loop {
             let mut exit_code = 0;
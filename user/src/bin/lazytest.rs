@@ -0,0 +1,81 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, mmap, munmap, pipe, read, waitpid, write, MAP_ANONYMOUS, MAP_FIXED,
+    MAP_PRIVATE, PROT_READ, PROT_WRITE, SIGSEGV,
+};
+
+const START: usize = 0x1000_0000;
+const PAGE_SIZE: usize = 4096;
+/// Far more than the physical memory, which is fine as long as few pages are touched.
+const LEN: usize = 256 * 1024 * 1024;
+
+/// Run `f` in a child process and return its exit code.
+fn run_child(f: fn()) -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        f();
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    exit_code
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let flags = MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED;
+    assert_eq!(
+        mmap(START, LEN, PROT_READ | PROT_WRITE, flags, -1, 0),
+        START as isize
+    );
+    let region = unsafe { core::slice::from_raw_parts_mut(START as *mut u8, LEN) };
+    // untouched pages read as zero
+    assert_eq!(region[0], 0);
+    assert_eq!(region[LEN / 2 + 123], 0);
+    for i in (0..LEN).step_by(LEN / 64) {
+        region[i] = (i / PAGE_SIZE) as u8;
+    }
+    for i in (0..LEN).step_by(LEN / 64) {
+        assert_eq!(region[i], (i / PAGE_SIZE) as u8);
+    }
+    // the kernel writes to an untouched page in read()
+    let mut pipe_fd = [0usize; 2];
+    pipe(&mut pipe_fd);
+    assert_eq!(write(pipe_fd[1], b"lazy"), 4);
+    close(pipe_fd[1]);
+    let buf = &mut region[LEN - PAGE_SIZE..LEN - PAGE_SIZE + 4];
+    assert_eq!(read(pipe_fd[0], buf), 4);
+    assert_eq!(buf, b"lazy");
+    close(pipe_fd[0]);
+    // a child sees touched pages and gets its own untouched ones
+    assert_eq!(
+        run_child(|| {
+            let region = unsafe { core::slice::from_raw_parts_mut(START as *mut u8, LEN) };
+            assert_eq!(region[LEN / 64], (LEN / 64 / PAGE_SIZE) as u8);
+            region[PAGE_SIZE * 3] = 1;
+        }),
+        0
+    );
+    assert_eq!(region[PAGE_SIZE * 3], 0);
+    assert_eq!(munmap(START, LEN), LEN as isize);
+    // faults outside any area still kill the process
+    assert_eq!(
+        run_child(|| unsafe {
+            (START as *mut u8).write_volatile(1);
+        }),
+        -SIGSEGV
+    );
+    assert_eq!(
+        run_child(|| unsafe {
+            ((START + LEN / 2) as *const u8).read_volatile();
+        }),
+        -SIGSEGV
+    );
+    println!("lazytest passed!");
+    0
+}
@@ -0,0 +1,101 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    exit, fork, mmap, waitpid, MAP_ANONYMOUS, MAP_FIXED, MAP_PRIVATE, MAP_SHARED, PROT_NONE,
+    PROT_READ, PROT_WRITE, SIGSEGV,
+};
+
+const PAGE_SIZE: usize = 4096;
+const EINVAL: isize = 22;
+const ENOMEM: isize = 12;
+const ENODEV: isize = 19;
+const RW: usize = PROT_READ | PROT_WRITE;
+
+/// Run `f` in a child process and return its exit code.
+fn run_child(f: fn()) -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        f();
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    exit_code
+}
+
+fn pages(start: isize, n: usize) -> &'static mut [u8] {
+    assert!(start > 0 && start as usize % PAGE_SIZE == 0);
+    unsafe { core::slice::from_raw_parts_mut(start as *mut u8, n * PAGE_SIZE) }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let anonymous = MAP_PRIVATE | MAP_ANONYMOUS;
+    // the kernel chooses addresses which do not overlap
+    let a = mmap(0, PAGE_SIZE * 3, RW, anonymous, -1, 0);
+    let b = mmap(0, PAGE_SIZE, RW, anonymous, -1, 0);
+    assert!(a > 0 && b > 0);
+    assert!(b + PAGE_SIZE as isize <= a || a + (PAGE_SIZE * 3) as isize <= b);
+    let a_pages = pages(a, 3);
+    a_pages.fill(1);
+    pages(b, 1).fill(2);
+    assert!(a_pages.iter().all(|&byte| byte == 1));
+    // a free hint is taken
+    let hint = 0x2000_0000;
+    assert_eq!(mmap(hint, PAGE_SIZE, RW, anonymous, -1, 0), hint as isize);
+    // MAP_FIXED replaces the middle page of an existing mapping
+    let middle = a as usize + PAGE_SIZE;
+    assert_eq!(
+        mmap(middle, PAGE_SIZE, RW, anonymous | MAP_FIXED, -1, 0),
+        middle as isize
+    );
+    assert_eq!(a_pages[0], 1);
+    assert_eq!(a_pages[PAGE_SIZE], 0);
+    assert_eq!(a_pages[PAGE_SIZE * 2], 1);
+    // a shared mapping is written by the child, while a private one is copied
+    static mut SHARED: isize = 0;
+    static mut PRIVATE: isize = 0;
+    unsafe {
+        SHARED = mmap(0, PAGE_SIZE, RW, MAP_SHARED | MAP_ANONYMOUS, -1, 0);
+        PRIVATE = mmap(0, PAGE_SIZE, RW, anonymous, -1, 0);
+        pages(PRIVATE, 1)[0] = 1;
+        assert_eq!(
+            run_child(|| {
+                pages(SHARED, 1)[0] = 42;
+                pages(PRIVATE, 1)[0] = 42;
+            }),
+            0
+        );
+        assert_eq!(pages(SHARED, 1)[0], 42);
+        assert_eq!(pages(PRIVATE, 1)[0], 1);
+    }
+    // pages of PROT_NONE cannot be touched
+    static mut GUARD: isize = 0;
+    unsafe {
+        GUARD = mmap(0, PAGE_SIZE, PROT_NONE, anonymous, -1, 0);
+        assert!(GUARD > 0);
+        assert_eq!(run_child(|| pages(GUARD, 1)[0] = 1), -SIGSEGV);
+    }
+    // invalid arguments
+    assert_eq!(mmap(0, 0, RW, anonymous, -1, 0), -EINVAL);
+    assert_eq!(mmap(0, PAGE_SIZE, RW, MAP_ANONYMOUS, -1, 0), -EINVAL);
+    assert_eq!(mmap(0, PAGE_SIZE, RW, anonymous, -1, 1), -EINVAL);
+    assert_eq!(
+        mmap(1, PAGE_SIZE, RW, anonymous | MAP_FIXED, -1, 0),
+        -EINVAL
+    );
+    // lengths which overflow when rounded up to pages
+    assert_eq!(mmap(0, usize::MAX, RW, anonymous, -1, 0), -ENOMEM);
+    assert_eq!(
+        mmap(PAGE_SIZE, usize::MAX, RW, anonymous | MAP_FIXED, -1, 0),
+        -ENOMEM
+    );
+    // stdin cannot be mapped
+    assert_eq!(mmap(0, PAGE_SIZE, RW, MAP_PRIVATE, 0, 0), -ENODEV);
+    println!("mmaptest passed!");
+    0
+}
@@ -0,0 +1,78 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    exit, fork, mmap, mprotect, msync, munmap, waitpid, MAP_ANONYMOUS, MAP_FIXED, MAP_PRIVATE,
+    MS_SYNC, PROT_NONE, PROT_READ, PROT_WRITE, SIGSEGV,
+};
+
+const PAGE_SIZE: usize = 4096;
+const START: usize = 0x3000_0000;
+const PAGES: usize = 4;
+const EINVAL: isize = 22;
+const ENOMEM: isize = 12;
+
+fn page(i: usize) -> &'static mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut((START + i * PAGE_SIZE) as *mut u8, PAGE_SIZE) }
+}
+
+/// Run `f` in a child process and return its exit code.
+fn run_child(f: fn()) -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        f();
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    exit_code
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let flags = MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED;
+    let len = PAGE_SIZE * PAGES;
+    assert_eq!(
+        mmap(START, len, PROT_READ | PROT_WRITE, flags, -1, 0),
+        START as isize
+    );
+    for i in 0..PAGES {
+        page(i).fill(i as u8 + 1);
+    }
+    // pages in the middle become read-only, while the others stay writable
+    assert_eq!(mprotect(START + PAGE_SIZE, PAGE_SIZE * 2, PROT_READ), 0);
+    assert_eq!(page(1)[0], 2);
+    assert_eq!(run_child(|| page(1)[0] = 0), -SIGSEGV);
+    assert_eq!(run_child(|| page(2)[PAGE_SIZE - 1] = 0), -SIGSEGV);
+    assert_eq!(run_child(|| page(3)[0] = 0), 0);
+    page(0)[0] = 1;
+    // then writable again
+    assert_eq!(mprotect(START, len, PROT_READ | PROT_WRITE), 0);
+    page(1)[0] = 2;
+    page(2)[0] = 3;
+    // a guard page cannot be touched at all
+    assert_eq!(mprotect(START, PAGE_SIZE, PROT_NONE), 0);
+    assert_eq!(run_child(|| assert_eq!(page(0)[0], 1)), -SIGSEGV);
+    assert_eq!(page(1)[0], 2);
+    // unmap a page in the middle
+    assert_eq!(munmap(START + PAGE_SIZE * 2, PAGE_SIZE), PAGE_SIZE as isize);
+    assert_eq!(run_child(|| assert_eq!(page(2)[0], 3)), -SIGSEGV);
+    assert!(page(1).iter().skip(1).all(|&byte| byte == 2));
+    assert!(page(3).iter().all(|&byte| byte == 4));
+    // the range should be mapped entirely
+    assert_eq!(mprotect(START, len, PROT_READ), -ENOMEM);
+    assert_eq!(mprotect(START + 1, PAGE_SIZE, PROT_READ), -EINVAL);
+    assert_eq!(munmap(START + 1, PAGE_SIZE), -EINVAL);
+    // lengths which overflow when rounded up to pages
+    assert_eq!(mprotect(START, usize::MAX, PROT_READ), -ENOMEM);
+    assert_eq!(msync(START, usize::MAX, MS_SYNC), -ENOMEM);
+    assert_eq!(munmap(START, usize::MAX), -EINVAL);
+    assert_eq!(munmap(START, usize::MAX - START), -EINVAL);
+    assert_eq!(munmap(START, len), len as isize);
+    assert_eq!(run_child(|| assert_eq!(page(3)[0], 4)), -SIGSEGV);
+    println!("mprotecttest passed!");
+    0
+}
@@ -0,0 +1,48 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, get_time, nanosleep, sleep, wait, TimeSpec};
+
+const EINVAL: isize = 22;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let start = get_time();
+    let req = TimeSpec {
+        sec: 0,
+        nsec: 50_000_000,
+    };
+    assert_eq!(nanosleep(&req, &mut TimeSpec::new()), 0);
+    assert!(get_time() - start >= 50);
+    let req = TimeSpec {
+        sec: 0,
+        nsec: 1_000_000_000,
+    };
+    assert_eq!(nanosleep(&req, &mut TimeSpec::new()), -EINVAL);
+    // negative seconds, or too many to count in microseconds
+    let req = TimeSpec {
+        sec: usize::MAX,
+        nsec: 0,
+    };
+    assert_eq!(nanosleep(&req, &mut TimeSpec::new()), -EINVAL);
+    // sleeping children do not take turns on the cpu
+    let start = get_time();
+    for i in 0..4 {
+        if fork() == 0 {
+            sleep(200 + i * 10);
+            exit(0);
+        }
+    }
+    let mut exit_code: i32 = 0;
+    for _ in 0..4 {
+        assert!(wait(&mut exit_code) > 0);
+        assert_eq!(exit_code, 0);
+    }
+    let elapsed = get_time() - start;
+    assert!((230..600).contains(&elapsed));
+    println!("nanosleeptest passed!");
+    0
+}
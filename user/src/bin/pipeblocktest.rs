@@ -0,0 +1,72 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, pipe, read, sigaction, sleep, waitpid, write, SignalAction, SignalFlags,
+    SIGPIPE, SIG_IGN,
+};
+
+const LEN: usize = 1000;
+const EPIPE: isize = 32;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let pid = fork();
+    if pid == 0 {
+        // blocks until the parent writes, then reads up to the end of the pipe
+        close(pipe_fd[1]);
+        let mut buffer = [0u8; 64];
+        let mut total = 0;
+        loop {
+            let len = read(pipe_fd[0], &mut buffer);
+            assert!(len >= 0);
+            if len == 0 {
+                break;
+            }
+            assert!(buffer[..len as usize].iter().all(|&byte| byte == 0x5a));
+            total += len as usize;
+        }
+        close(pipe_fd[0]);
+        exit(if total == LEN { 0 } else { -1 });
+    }
+    close(pipe_fd[0]);
+    sleep(50);
+    // the pipe fills up long before all is written, so the parent blocks in between
+    assert_eq!(write(pipe_fd[1], &[0x5a; LEN]), LEN as isize);
+    close(pipe_fd[1]);
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+
+    // a writer blocked on a full pipe wakes up once the read end is closed
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let pid = fork();
+    if pid == 0 {
+        close(pipe_fd[1]);
+        sleep(50);
+        close(pipe_fd[0]);
+        exit(0);
+    }
+    close(pipe_fd[0]);
+    let pipe_writer = fork();
+    if pipe_writer == 0 {
+        // killed by SIGPIPE
+        write(pipe_fd[1], &[0x5a; LEN]);
+        exit(0);
+    }
+    assert_eq!(waitpid(pipe_writer, &mut exit_code), pipe_writer);
+    assert_eq!(exit_code, -SIGPIPE);
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    // with SIGPIPE ignored, the write fails instead
+    let action = SignalAction::new(SIG_IGN, SignalFlags::empty());
+    assert_eq!(sigaction(SIGPIPE, Some(&action), None), 0);
+    assert_eq!(write(pipe_fd[1], &[0x5a]), -EPIPE);
+    close(pipe_fd[1]);
+    println!("pipeblocktest passed!");
+    0
+}
@@ -4,13 +4,13 @@
 #[macro_use]
 extern crate user_lib;
 
-use user_lib::{fork, exec, wait};
+use user_lib::{exec, fork, wait};
 
 #[no_mangle]
 pub fn main() -> i32 {
     for i in 0..1000 {
         if fork() == 0 {
-            exec("pipe_large_test\0");
+            exec("pipe_large_test\0", &["pipe_large_test\0"]);
         } else {
             let mut _unused: i32 = 0;
             wait(&mut _unused);
@@ -18,4 +18,4 @@ pub fn main() -> i32 {
         }
     }
     0
-}
\ No newline at end of file
+}
@@ -0,0 +1,69 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, lseek, open, pipe, pread, pwrite, read, unlink, write, OpenFlags, SEEK_CUR, SEEK_END,
+    SEEK_SET,
+};
+
+const EFBIG: isize = 27;
+const ESPIPE: isize = 29;
+/// Beyond the maximum file size.
+const TOO_FAR: usize = 1 << 40;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open(
+        "seek_a\0",
+        OpenFlags::CREAT | OpenFlags::RDWR | OpenFlags::TRUNC,
+    );
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"0123456789"), 10);
+    assert_eq!(lseek(fd, 0, SEEK_CUR), 10);
+    assert_eq!(lseek(fd, 2, SEEK_SET), 2);
+    let mut buffer = [0u8; 16];
+    assert_eq!(read(fd, &mut buffer[..3]), 3);
+    assert_eq!(&buffer[..3], b"234");
+    assert_eq!(lseek(fd, -1, SEEK_END), 9);
+    assert_eq!(read(fd, &mut buffer), 1);
+    assert_eq!(buffer[0], b'9');
+    assert!(lseek(fd, -100, SEEK_CUR) < 0);
+    assert!(lseek(fd, 0, 3) < 0);
+
+    // positional io leaves the offset alone
+    assert_eq!(lseek(fd, 4, SEEK_SET), 4);
+    assert_eq!(pwrite(fd, b"ab", 0), 2);
+    assert_eq!(pread(fd, &mut buffer[..4], 0), 4);
+    assert_eq!(&buffer[..4], b"ab23");
+    assert_eq!(pread(fd, &mut buffer, 100), 0);
+    assert_eq!(lseek(fd, 0, SEEK_CUR), 4);
+
+    // writing beyond the end leaves a hole of zeros
+    assert_eq!(lseek(fd, 20, SEEK_SET), 20);
+    assert_eq!(write(fd, b"x"), 1);
+    assert_eq!(lseek(fd, 0, SEEK_END), 21);
+    assert_eq!(pread(fd, &mut buffer, 8), 13);
+    assert!(buffer[2..12].iter().all(|&b| b == 0));
+    assert_eq!(buffer[12], b'x');
+
+    // nothing can be written beyond the maximum file size
+    assert_eq!(lseek(fd, TOO_FAR as isize, SEEK_SET), TOO_FAR as isize);
+    assert_eq!(write(fd, b"x"), -EFBIG);
+    assert_eq!(pwrite(fd, b"x", TOO_FAR), -EFBIG);
+    close(fd);
+    unlink("seek_a\0");
+
+    let mut pipe_fd = [0usize; 2];
+    pipe(&mut pipe_fd);
+    assert_eq!(lseek(pipe_fd[0], 0, SEEK_SET), -ESPIPE);
+    assert_eq!(pwrite(pipe_fd[1], b"a", 0), -ESPIPE);
+    close(pipe_fd[0]);
+    close(pipe_fd[1]);
+    assert_eq!(lseek(0, 0, SEEK_CUR), -ESPIPE);
+    println!("seektest passed!");
+    0
+}
@@ -0,0 +1,183 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicIsize, AtomicUsize, Ordering};
+use user_lib::{
+    exit, fork, getpid, kill, mmap, nanosleep, sigaction, sigprocmask, sigreturn, sleep, waitpid,
+    yield_, SignalAction, SignalFlags, TimeSpec, MAP_ANONYMOUS, MAP_SHARED, PROT_READ, PROT_WRITE,
+    SIGCONT, SIGKILL, SIGSEGV, SIGSTOP, SIGTERM, SIGUSR1, SIGUSR2, SIG_BLOCK, SIG_IGN, SIG_SETMASK,
+    SIG_UNBLOCK,
+};
+
+const ESRCH: isize = 3;
+const EINTR: isize = 4;
+const EINVAL: isize = 22;
+
+static HANDLED: AtomicUsize = AtomicUsize::new(0);
+static FORKED: AtomicIsize = AtomicIsize::new(-1);
+
+extern "C" fn on_usr1(signum: i32) {
+    assert_eq!(signum, SIGUSR1);
+    HANDLED.fetch_add(1, Ordering::SeqCst);
+}
+
+extern "C" fn on_usr2(signum: i32) {
+    assert_eq!(signum, SIGUSR2);
+    // SIGUSR1 waits for this handler to return, rather than interrupting the sleep
+    assert_eq!(kill(getpid(), SIGUSR1), 0);
+    let req = TimeSpec {
+        sec: 0,
+        nsec: 10_000_000,
+    };
+    assert_eq!(nanosleep(&req, &mut TimeSpec::new()), 0);
+    assert_eq!(HANDLED.load(Ordering::SeqCst), 2);
+}
+
+extern "C" fn fork_in_handler(_: i32) {
+    FORKED.store(fork(), Ordering::SeqCst);
+}
+
+extern "C" fn on_segv(signum: i32) {
+    exit(signum + 100);
+}
+
+fn set_handler(signum: i32, handler: usize) {
+    let action = SignalAction::new(handler, SignalFlags::empty());
+    assert_eq!(sigaction(signum, Some(&action), None), 0);
+}
+
+fn wait_child(pid: isize) -> i32 {
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    exit_code
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = getpid();
+    // the handler runs before kill returns
+    set_handler(SIGUSR1, on_usr1 as usize);
+    assert_eq!(kill(pid, SIGUSR1), 0);
+    assert_eq!(HANDLED.load(Ordering::SeqCst), 1);
+    let mut old_action = SignalAction::default();
+    assert_eq!(sigaction(SIGUSR1, None, Some(&mut old_action)), 0);
+    assert_eq!(old_action.handler, on_usr1 as usize);
+    // blocked signals are delivered once unblocked
+    let mut old_set = SignalFlags::all();
+    assert_eq!(
+        sigprocmask(SIG_BLOCK, Some(SignalFlags::SIGUSR1), Some(&mut old_set)),
+        0
+    );
+    assert!(old_set.is_empty());
+    assert_eq!(kill(pid, SIGUSR1), 0);
+    assert_eq!(HANDLED.load(Ordering::SeqCst), 1);
+    assert_eq!(
+        sigprocmask(SIG_UNBLOCK, Some(SignalFlags::SIGUSR1), None),
+        0
+    );
+    assert_eq!(HANDLED.load(Ordering::SeqCst), 2);
+    // SIGKILL and SIGSTOP cannot be blocked
+    let set = SignalFlags::SIGKILL | SignalFlags::SIGSTOP;
+    assert_eq!(sigprocmask(SIG_SETMASK, Some(set), None), 0);
+    assert_eq!(sigprocmask(SIG_BLOCK, None, Some(&mut old_set)), 0);
+    assert!(old_set.is_empty());
+    // ignored signals are discarded
+    set_handler(SIGUSR2, SIG_IGN);
+    assert_eq!(kill(pid, SIGUSR2), 0);
+    // the default action of SIGTERM terminates a sleeping child
+    let child = fork();
+    if child == 0 {
+        sleep(10000);
+        exit(0);
+    }
+    sleep(50);
+    assert_eq!(kill(child, SIGTERM), 0);
+    assert_eq!(wait_child(child), -SIGTERM);
+    // a handler interrupts nanosleep
+    let child = fork();
+    if child == 0 {
+        let req = TimeSpec { sec: 10, nsec: 0 };
+        let mut rem = TimeSpec::new();
+        assert_eq!(nanosleep(&req, &mut rem), -EINTR);
+        assert_eq!(HANDLED.load(Ordering::SeqCst), 3);
+        assert!(rem.sec > 0 && rem.sec < 10);
+        exit(0);
+    }
+    sleep(50);
+    assert_eq!(kill(child, SIGUSR1), 0);
+    assert_eq!(wait_child(child), 0);
+    // but not one in another handler
+    let child = fork();
+    if child == 0 {
+        set_handler(SIGUSR2, on_usr2 as usize);
+        assert_eq!(kill(getpid(), SIGUSR2), 0);
+        assert_eq!(HANDLED.load(Ordering::SeqCst), 3);
+        exit(0);
+    }
+    assert_eq!(wait_child(child), 0);
+    // a process forked in a handler returns from it as well
+    let child = fork();
+    if child == 0 {
+        set_handler(SIGUSR2, fork_in_handler as usize);
+        assert_eq!(kill(getpid(), SIGUSR2), 0);
+        let forked = FORKED.load(Ordering::SeqCst);
+        if forked == 0 {
+            exit(9);
+        }
+        assert_eq!(wait_child(forked), 9);
+        exit(0);
+    }
+    assert_eq!(wait_child(child), 0);
+    // a stopped child does not run until SIGCONT
+    let counter = mmap(
+        0,
+        4096,
+        PROT_READ | PROT_WRITE,
+        MAP_SHARED | MAP_ANONYMOUS,
+        -1,
+        0,
+    );
+    assert!(counter > 0);
+    let counter = unsafe { &*(counter as *const AtomicUsize) };
+    let child = fork();
+    if child == 0 {
+        loop {
+            counter.fetch_add(1, Ordering::SeqCst);
+            yield_();
+        }
+    }
+    assert_eq!(kill(child, SIGSTOP), 0);
+    sleep(50);
+    let count = counter.load(Ordering::SeqCst);
+    sleep(50);
+    assert_eq!(counter.load(Ordering::SeqCst), count);
+    assert_eq!(kill(child, SIGCONT), 0);
+    sleep(50);
+    assert!(counter.load(Ordering::SeqCst) > count);
+    assert_eq!(kill(child, SIGKILL), 0);
+    assert_eq!(wait_child(child), -SIGKILL);
+    // faults are caught as SIGSEGV
+    let child = fork();
+    if child == 0 {
+        set_handler(SIGSEGV, on_segv as usize);
+        unsafe {
+            core::ptr::null::<u8>().read_volatile();
+        }
+        exit(0);
+    }
+    assert_eq!(wait_child(child), SIGSEGV + 100);
+    // invalid arguments
+    assert_eq!(kill(pid, 32), -EINVAL);
+    assert_eq!(kill(0x7fff_ffff, 0), -ESRCH);
+    assert_eq!(kill(pid, 0), 0);
+    let action = SignalAction::default();
+    assert_eq!(sigaction(SIGKILL, Some(&action), None), -EINVAL);
+    assert_eq!(sigaction(0, None, None), -EINVAL);
+    assert_eq!(sigprocmask(3, Some(SignalFlags::empty()), None), -EINVAL);
+    assert_eq!(sigreturn(), -EINVAL);
+    println!("sigtest passed!");
+    0
+}
@@ -0,0 +1,61 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, waitpid, SIGSEGV};
+
+/// Run `f` in a child process and return its exit code.
+fn run_child(f: fn()) -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        f();
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    exit_code
+}
+
+/// Fill a local array of `LEN` bytes, which is larger than the initial user stack.
+fn fill_stack<const LEN: usize>() -> usize {
+    let mut buf = [0u8; LEN];
+    for (i, byte) in buf.iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    buf.iter().map(|&byte| byte as usize).sum()
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // the stack grows on page faults
+    assert_eq!(fill_stack::<{ 32 * 1024 }>(), 255 * 128 * 128);
+    // and the child gets a copy of the grown stack
+    assert_eq!(
+        run_child(|| assert_eq!(fill_stack::<{ 32 * 1024 }>(), 255 * 128 * 128)),
+        0
+    );
+    // but not beyond its limit
+    assert_eq!(
+        run_child(|| {
+            fill_stack::<{ 128 * 1024 }>();
+        }),
+        -SIGSEGV
+    );
+    // faults violating permissions or outside any area kill the process
+    assert_eq!(
+        run_child(|| unsafe {
+            (main as usize as *mut u8).write_volatile(0);
+        }),
+        -SIGSEGV
+    );
+    assert_eq!(
+        run_child(|| unsafe {
+            core::ptr::null::<u8>().read_volatile();
+        }),
+        -SIGSEGV
+    );
+    println!("stacktest passed!");
+    0
+}
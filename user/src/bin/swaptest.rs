@@ -0,0 +1,92 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, mmap, munmap, pipe, read, waitpid, write, MAP_ANONYMOUS, MAP_FIXED,
+    MAP_PRIVATE, PROT_READ, PROT_WRITE,
+};
+
+const START: usize = 0x1000_0000;
+const PAGE_SIZE: usize = 4096;
+/// More than the frames available, so that pages are swapped out.
+const LEN: usize = 6 * 1024 * 1024;
+
+fn page(i: usize) -> &'static mut [usize] {
+    unsafe { core::slice::from_raw_parts_mut((START + i * PAGE_SIZE) as *mut usize, 2) }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let flags = MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED;
+    assert_eq!(
+        mmap(START, LEN, PROT_READ | PROT_WRITE, flags, -1, 0),
+        START as isize
+    );
+    let pages = LEN / PAGE_SIZE;
+    for i in 0..pages {
+        page(i)[0] = i;
+        page(i)[1] = !i;
+    }
+    for i in 0..pages {
+        assert_eq!(page(i), &[i, !i]);
+    }
+    println!("swaptest: {} pages written and read back", pages);
+    // pages swapped out are shared with the child as well, even if the kernel writes them
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    assert_eq!(write(pipe_fd[1], b"written"), 7);
+    let pid = fork();
+    if pid == 0 {
+        let buf = unsafe { core::slice::from_raw_parts_mut((START + PAGE_SIZE) as *mut u8, 7) };
+        assert_eq!(read(pipe_fd[0], buf), 7);
+        assert_eq!(buf, b"written");
+        for i in (0..pages).step_by(7) {
+            assert_eq!(page(i), &[i, !i]);
+            page(i)[0] = 0;
+        }
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    for i in 0..pages {
+        assert_eq!(page(i), &[i, !i]);
+    }
+    assert_eq!(munmap(START, LEN), LEN as isize);
+    close(pipe_fd[0]);
+    close(pipe_fd[1]);
+    // the buffer of a blocked read is kept while the child pushes other pages out
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let pid = fork();
+    if pid == 0 {
+        close(pipe_fd[0]);
+        assert_eq!(
+            mmap(START, LEN, PROT_READ | PROT_WRITE, flags, -1, 0),
+            START as isize
+        );
+        for _ in 0..2 {
+            for i in 0..pages {
+                page(i)[0] = i;
+            }
+        }
+        assert_eq!(write(pipe_fd[1], b"swapped"), 7);
+        exit(0);
+    }
+    close(pipe_fd[1]);
+    assert_eq!(
+        mmap(START, PAGE_SIZE, PROT_READ | PROT_WRITE, flags, -1, 0),
+        START as isize
+    );
+    let buf = unsafe { core::slice::from_raw_parts_mut(START as *mut u8, 7) };
+    assert_eq!(read(pipe_fd[0], buf), 7);
+    assert_eq!(buf, b"swapped");
+    close(pipe_fd[0]);
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(munmap(START, PAGE_SIZE), PAGE_SIZE as isize);
+    println!("swaptest passed!");
+    0
+}
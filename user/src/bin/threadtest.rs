@@ -0,0 +1,175 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{
+    close, exit, fork, gettid, kill, mmap, munmap, pipe, read, sigprocmask, thread_create, waitpid,
+    waittid, yield_, SignalFlags, MAP_ANONYMOUS, MAP_PRIVATE, PROT_READ, PROT_WRITE, SIGTERM,
+    SIG_BLOCK, SIG_UNBLOCK,
+};
+
+const ESRCH: isize = 3;
+const EDEADLK: isize = 35;
+
+const THREAD_NUM: usize = 4;
+const ADD_TIMES: usize = 1000;
+const PAGE_SIZE: usize = 4096;
+const USER_STACK_LIMIT: usize = PAGE_SIZE * 16;
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+/// A page of the stack of a thread blocked in `read_pipe`.
+static STACK_PAGE: AtomicUsize = AtomicUsize::new(0);
+
+/// The page of the stack the caller is running on.
+fn stack_page() -> usize {
+    let local = 0u8;
+    &local as *const u8 as usize / PAGE_SIZE * PAGE_SIZE
+}
+
+/// Map a page at `start` if it is free, return whether it was.
+fn map_if_free(start: usize) -> bool {
+    let flags = MAP_PRIVATE | MAP_ANONYMOUS;
+    let mapped = mmap(start, PAGE_SIZE, PROT_READ | PROT_WRITE, flags, -1, 0);
+    assert!(mapped > 0);
+    assert_eq!(munmap(mapped as usize, PAGE_SIZE), PAGE_SIZE as isize);
+    mapped as usize == start
+}
+
+fn add(times: usize) -> i32 {
+    for i in 0..times {
+        COUNTER.fetch_add(1, Ordering::SeqCst);
+        if i % 100 == 0 {
+            yield_();
+        }
+    }
+    gettid() as i32
+}
+
+fn spin(_: usize) -> i32 {
+    loop {
+        yield_();
+    }
+}
+
+/// Block on a pipe until it is closed.
+fn read_pipe(fd: usize) -> i32 {
+    STACK_PAGE.store(stack_page(), Ordering::SeqCst);
+    let mut buffer = [0u8; 1];
+    read(fd, &mut buffer);
+    0
+}
+
+fn fork_in_thread(_: usize) -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        // the forking thread becomes the main thread of the child, while the stacks of
+        // other threads are gone
+        let stack_freed = map_if_free(STACK_PAGE.load(Ordering::SeqCst));
+        exit(if gettid() == 0 && stack_freed { 7 } else { 1 });
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    exit_code
+}
+
+/// The page below the stack is kept free of mappings, to catch overflows.
+fn map_below_stack(_: usize) -> i32 {
+    // the thread starts from the top page of its stack
+    let guard = stack_page() + PAGE_SIZE - USER_STACK_LIMIT - PAGE_SIZE;
+    if map_if_free(guard) {
+        1
+    } else {
+        0
+    }
+}
+
+/// The signal mask is inherited from the creating thread, and changed for this one only.
+fn unblock_in_thread(_: usize) -> i32 {
+    let mut old_set = SignalFlags::empty();
+    assert_eq!(
+        sigprocmask(SIG_UNBLOCK, Some(SignalFlags::SIGUSR1), Some(&mut old_set)),
+        0
+    );
+    assert_eq!(old_set, SignalFlags::SIGUSR1);
+    0
+}
+
+fn wait_thread(tid: isize) -> i32 {
+    let mut exit_code: i32 = 0;
+    assert_eq!(waittid(tid as usize, &mut exit_code), tid);
+    exit_code
+}
+
+fn wait_child(pid: isize) -> i32 {
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    exit_code
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(gettid(), 0);
+    // threads share the address space, and exit with their own codes
+    let mut tids = [0; THREAD_NUM];
+    for tid in tids.iter_mut() {
+        *tid = thread_create(add, ADD_TIMES);
+        assert!(*tid > 0);
+    }
+    for &tid in tids.iter() {
+        assert_eq!(wait_thread(tid), tid as i32);
+    }
+    assert_eq!(COUNTER.load(Ordering::SeqCst), THREAD_NUM * ADD_TIMES);
+    // joined threads are gone, and a thread cannot join itself
+    assert_eq!(waittid(tids[0] as usize, &mut 0), -ESRCH);
+    assert_eq!(waittid(100, &mut 0), -ESRCH);
+    assert_eq!(waittid(0, &mut 0), -EDEADLK);
+    // fork copies only the calling thread
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let reader = thread_create(read_pipe, pipe_fd[0]);
+    while STACK_PAGE.load(Ordering::SeqCst) == 0 {
+        yield_();
+    }
+    let tid = thread_create(fork_in_thread, 0);
+    assert_eq!(wait_thread(tid), 7);
+    close(pipe_fd[1]);
+    assert_eq!(wait_thread(reader), 0);
+    close(pipe_fd[0]);
+    let tid = thread_create(map_below_stack, 0);
+    assert_eq!(wait_thread(tid), 0);
+    // each thread has its own signal mask
+    assert_eq!(sigprocmask(SIG_BLOCK, Some(SignalFlags::SIGUSR1), None), 0);
+    let tid = thread_create(unblock_in_thread, 0);
+    assert_eq!(wait_thread(tid), 0);
+    let mut old_set = SignalFlags::empty();
+    assert_eq!(
+        sigprocmask(SIG_UNBLOCK, Some(SignalFlags::SIGUSR1), Some(&mut old_set)),
+        0
+    );
+    assert_eq!(old_set, SignalFlags::SIGUSR1);
+    // the process exits with its main thread, which ends other threads
+    let pid = fork();
+    if pid == 0 {
+        assert!(thread_create(spin, 0) > 0);
+        let mut pipe_fd = [0usize; 2];
+        assert_eq!(pipe(&mut pipe_fd), 0);
+        assert!(thread_create(read_pipe, pipe_fd[0]) > 0);
+        yield_();
+        exit(5);
+    }
+    assert_eq!(wait_child(pid), 5);
+    // signals terminate all threads of the process
+    let pid = fork();
+    if pid == 0 {
+        assert!(thread_create(spin, 0) > 0);
+        spin(0);
+    }
+    yield_();
+    assert_eq!(kill(pid, SIGTERM), 0);
+    assert_eq!(wait_child(pid), -SIGTERM);
+    println!("threadtest passed!");
+    0
+}
@@ -5,49 +5,392 @@
 extern crate user_lib;
 extern crate alloc;
 
-use alloc::string::String;
-use user_lib::{console::getchar, exec, exit, fork, waitpid};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use user_lib::{
+    chdir, close, console::getchar, dup3, environ, execve, exit, fork, getcwd, getdents, mkdir,
+    open, parse_dirents, pipe, read, rmdir, try_waitpid, unlink, waitpid, write, OpenFlags, DT_DIR,
+};
 
 const LF: u8 = 0x0a;
 const CR: u8 = 0x0d;
 const BS: u8 = 0x08;
 const DL: u8 = 0x7f;
+const EISDIR: isize = 21;
+
+/// A program with its arguments and redirections, one stage of a pipeline.
+#[derive(Default)]
+struct Command {
+    args: Vec<String>,
+    input: Option<String>,
+    output: Option<String>,
+    append: bool,
+}
+
+/// Split `line` into words and the operators `|`, `<`, `>`, `>>` and `&`.
+fn tokenize(line: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut word = String::new();
+    let mut chars = line.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch.is_whitespace() || "|<>&".contains(ch) {
+            if !word.is_empty() {
+                tokens.push(word.clone());
+                word.clear();
+            }
+            if ch == '>' && chars.peek() == Some(&'>') {
+                chars.next();
+                tokens.push(String::from(">>"));
+            } else if !ch.is_whitespace() {
+                tokens.push(ch.to_string());
+            }
+        } else {
+            word.push(ch);
+        }
+    }
+    if !word.is_empty() {
+        tokens.push(word);
+    }
+    tokens
+}
+
+/// Return the commands of a pipeline, and whether it runs in background.
+fn parse(line: &str) -> Result<(Vec<Command>, bool), &'static str> {
+    let mut tokens = tokenize(line);
+    let background = tokens.last().map(|token| token.as_str()) == Some("&");
+    if background {
+        tokens.pop();
+    }
+    let mut commands: Vec<Command> = Vec::new();
+    let mut command = Command::default();
+    let mut tokens = tokens.into_iter();
+    while let Some(token) = tokens.next() {
+        match token.as_str() {
+            "|" => {
+                if command.args.is_empty() {
+                    return Err("empty command in pipeline");
+                }
+                commands.push(command);
+                command = Command::default();
+            }
+            "<" | ">" | ">>" => {
+                let path = match tokens.next() {
+                    Some(path) if !matches!(path.as_str(), "|" | "<" | ">" | ">>" | "&") => path,
+                    _ => return Err("missing file for redirection"),
+                };
+                if token == "<" {
+                    command.input = Some(path);
+                } else {
+                    command.output = Some(path);
+                    command.append = token == ">>";
+                }
+            }
+            "&" => return Err("unexpected &"),
+            _ => command.args.push(token),
+        }
+    }
+    if command.args.is_empty() {
+        if commands.is_empty() && command.input.is_none() && command.output.is_none() {
+            return Ok((commands, background));
+        }
+        return Err("empty command in pipeline");
+    }
+    commands.push(command);
+    Ok((commands, background))
+}
+
+/// Whether `env`, in the form of `NAME=VALUE`, is named `name`.
+fn is_env(env: &str, name: &str) -> bool {
+    env.starts_with(name) && env[name.len()..].starts_with('=')
+}
+
+fn get_env<'a>(envs: &'a [String], name: &str) -> Option<&'a str> {
+    envs.iter()
+        .find(|env| is_env(env, name))
+        .map(|env| &env[name.len() + 1..])
+}
+
+/// Add or replace the variable `var`, whose name ends at `pos`.
+fn set_env(envs: &mut Vec<String>, var: &str, pos: usize) {
+    let name = &var[..pos];
+    match envs.iter_mut().find(|env| is_env(env, name)) {
+        Some(env) => *env = String::from(var),
+        None => envs.push(String::from(var)),
+    }
+}
+
+/// Replace `fd` with the opened file, return false if it cannot be opened.
+fn redirect(path: &str, flags: OpenFlags, fd: usize) -> bool {
+    let file_fd = open((String::from(path) + "\0").as_str(), flags);
+    if file_fd < 0 {
+        println!("Shell: cannot open {}", path);
+        return false;
+    }
+    dup3(file_fd as usize, fd, OpenFlags::empty());
+    close(file_fd as usize);
+    true
+}
+
+fn ls(path: &str) -> i32 {
+    let fd = open(
+        (String::from(path) + "\0").as_str(),
+        OpenFlags::RDONLY | OpenFlags::DIRECTORY,
+    );
+    if fd < 0 {
+        println!("ls: cannot access {}", path);
+        return 1;
+    }
+    let mut buffer = [0u8; 512];
+    loop {
+        let len = getdents(fd as usize, &mut buffer);
+        if len <= 0 {
+            break;
+        }
+        for dirent in parse_dirents(&buffer[..len as usize]) {
+            if dirent.type_ == DT_DIR {
+                println!("{}/", dirent.name);
+            } else {
+                println!("{}", dirent.name);
+            }
+        }
+    }
+    close(fd as usize);
+    0
+}
+
+/// Remove a file or an empty directory.
+fn rm(path: &str) -> i32 {
+    let path_z = String::from(path) + "\0";
+    let mut result = unlink(path_z.as_str());
+    if result == -EISDIR {
+        result = rmdir(path_z.as_str());
+    }
+    if result < 0 {
+        println!("rm: cannot remove {}", path);
+        return 1;
+    }
+    0
+}
+
+/// Copy `fd` to stdout until the end.
+fn cat(fd: usize) {
+    let mut buffer = [0u8; 256];
+    loop {
+        let len = read(fd, &mut buffer);
+        if len <= 0 {
+            break;
+        }
+        write(1, &buffer[..len as usize]);
+    }
+}
+
+/// Run a builtin in a forked child, return None if `args` is not one.
+fn run_builtin(args: &[&str], envs: &[String]) -> Option<i32> {
+    let exit_code = match args {
+        ["env"] => {
+            for env in envs {
+                println!("{}", env);
+            }
+            0
+        }
+        ["pwd"] => {
+            let mut buffer = [0u8; 256];
+            let len = getcwd(&mut buffer);
+            if len > 0 {
+                let cwd = core::str::from_utf8(&buffer[..len as usize - 1]).unwrap();
+                println!("{}", cwd);
+            }
+            0
+        }
+        ["echo", words @ ..] => {
+            for (i, word) in words.iter().enumerate() {
+                if i > 0 {
+                    print!(" ");
+                }
+                print!("{}", word);
+            }
+            println!("");
+            0
+        }
+        ["cat"] => {
+            cat(0);
+            0
+        }
+        ["cat", paths @ ..] => {
+            let mut exit_code = 0;
+            for path in paths {
+                let fd = open((String::from(*path) + "\0").as_str(), OpenFlags::RDONLY);
+                if fd < 0 {
+                    println!("cat: cannot open {}", path);
+                    exit_code = 1;
+                    continue;
+                }
+                cat(fd as usize);
+                close(fd as usize);
+            }
+            exit_code
+        }
+        ["ls"] => ls("."),
+        ["ls", paths @ ..] => paths.iter().map(|path| ls(path)).max().unwrap(),
+        ["mkdir", paths @ ..] => {
+            let mut exit_code = 0;
+            for path in paths {
+                if mkdir((String::from(*path) + "\0").as_str()) < 0 {
+                    println!("mkdir: cannot create {}", path);
+                    exit_code = 1;
+                }
+            }
+            exit_code
+        }
+        ["rm", paths @ ..] => paths.iter().map(|path| rm(path)).max().unwrap_or(0),
+        _ => return None,
+    };
+    Some(exit_code)
+}
+
+/// Fork a child for each command with pipes between them, return their pids.
+fn run_pipeline(commands: &[Command], envs: &[String]) -> Vec<isize> {
+    let mut pids: Vec<isize> = Vec::new();
+    let mut prev_read_fd: Option<usize> = None;
+    for (i, command) in commands.iter().enumerate() {
+        let pipe_fd = if i + 1 < commands.len() {
+            let mut pipe_fd = [0usize; 2];
+            pipe(&mut pipe_fd);
+            Some(pipe_fd)
+        } else {
+            None
+        };
+        let pid = fork();
+        if pid == 0 {
+            // child process
+            if let Some(read_fd) = prev_read_fd {
+                dup3(read_fd, 0, OpenFlags::empty());
+                close(read_fd);
+            }
+            if let Some(pipe_fd) = pipe_fd {
+                dup3(pipe_fd[1], 1, OpenFlags::empty());
+                close(pipe_fd[0]);
+                close(pipe_fd[1]);
+            }
+            if let Some(input) = &command.input {
+                if !redirect(input, OpenFlags::RDONLY, 0) {
+                    exit(-4);
+                }
+            }
+            if let Some(output) = &command.output {
+                let mode = if command.append {
+                    OpenFlags::APPEND
+                } else {
+                    OpenFlags::TRUNC
+                };
+                if !redirect(output, OpenFlags::CREAT | OpenFlags::WRONLY | mode, 1) {
+                    exit(-4);
+                }
+            }
+            let args: Vec<&str> = command.args.iter().map(|arg| arg.as_str()).collect();
+            if let Some(exit_code) = run_builtin(&args, envs) {
+                exit(exit_code);
+            }
+            let args: Vec<String> = command.args.iter().map(|arg| arg.clone() + "\0").collect();
+            let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+            let envs: Vec<String> = envs.iter().map(|env| env.clone() + "\0").collect();
+            let envs: Vec<&str> = envs.iter().map(|env| env.as_str()).collect();
+            if execve(args[0], &args, &envs) == -1 {
+                println!("Error when executing!");
+                exit(-4);
+            }
+            unreachable!();
+        }
+        // parent process, close fds which only children use
+        if let Some(read_fd) = prev_read_fd {
+            close(read_fd);
+        }
+        prev_read_fd = pipe_fd.map(|pipe_fd| {
+            close(pipe_fd[1]);
+            pipe_fd[0]
+        });
+        pids.push(pid);
+    }
+    pids
+}
 
 #[no_mangle]
 pub fn main() -> i32 {
     println!("Rust user shell");
     let mut line = String::new();
+    let mut jobs: Vec<isize> = Vec::new();
+    // environment of the shell, passed to every command
+    let mut envs: Vec<String> = environ().into_iter().map(String::from).collect();
     print!(">> ");
     loop {
         match getchar() {
             LF | CR => {
                 println!("");
-                if !line.is_empty() {
-                    match line.as_str() {
-                        "quit" => {
-                            exit(0);
-                        }
-                        _ => {
-                            line.push('\0');
-                            let pid = fork();
-                            if pid == 0 {
-                                // child process
-                                if exec(line.as_str()) == -1 {
-                                    println!("Error when executing!");
-                                    return -4;
+                match parse(line.as_str()) {
+                    Ok((commands, background)) => {
+                        let args: Vec<&str> = match commands.as_slice() {
+                            [command] => command.args.iter().map(|arg| arg.as_str()).collect(),
+                            _ => Vec::new(),
+                        };
+                        // builtins which change the shell itself
+                        match args.as_slice() {
+                            ["exit"] | ["quit"] => {
+                                exit(0);
+                            }
+                            ["cd"] => {
+                                let home = get_env(&envs, "HOME").unwrap_or("/");
+                                if chdir((String::from(home) + "\0").as_str()) < 0 {
+                                    println!("cd: cannot access {}", home);
+                                }
+                            }
+                            ["export", vars @ ..] => {
+                                for var in vars {
+                                    match var.find('=') {
+                                        Some(pos) if pos > 0 => set_env(&mut envs, var, pos),
+                                        _ => println!("export: invalid variable {}", var),
+                                    }
+                                }
+                            }
+                            ["unset", names @ ..] => {
+                                envs.retain(|env| !names.iter().any(|name| is_env(env, name)));
+                            }
+                            ["cd", path] => {
+                                if chdir((String::from(*path) + "\0").as_str()) < 0 {
+                                    println!("cd: cannot access {}", path);
+                                }
+                            }
+                            _ if commands.is_empty() => {}
+                            _ if background => {
+                                let pids = run_pipeline(&commands, &envs);
+                                println!("[{}]", pids.last().unwrap());
+                                jobs.extend(pids);
+                            }
+                            _ => {
+                                for pid in run_pipeline(&commands, &envs) {
+                                    let mut exit_code = 0;
+                                    let exit_pid = waitpid(pid, &mut exit_code);
+                                    assert_eq!(pid, exit_pid);
+                                    println!(
+                                        "Shell: Process {} exited with code {}",
+                                        pid, exit_code
+                                    );
                                 }
-                                unreachable!();
-                            } else {
-                                // parent process
-                                let mut exit_code = 0;
-                                let exit_pid = waitpid(pid, &mut exit_code);
-                                assert_eq!(pid, exit_pid);
-                                println!("Shell: Process {} exited with code {}", pid, exit_code);
                             }
-                            line.clear();
                         }
                     }
+                    Err(err) => println!("Shell: {}", err),
                 }
+                line.clear();
+                // reap background jobs which have finished
+                jobs.retain(|&pid| {
+                    let mut exit_code = 0;
+                    if try_waitpid(pid, &mut exit_code) == pid {
+                        println!("[{}] Done, exited with code {}", pid, exit_code);
+                        false
+                    } else {
+                        true
+                    }
+                });
                 print!(">> ");
             }
             BS | DL => {
@@ -5,16 +5,37 @@
 extern crate user_lib;
 
 static TESTS: &[&str] = &[
+    "argtest\0",
+    "brktest\0",
+    "cowtest\0",
+    "cwdtest\0",
+    "dirtest\0",
+    "duptest\0",
+    "envtest\0",
     "exit\0",
     "fantastic_text\0",
+    "filemaptest\0",
+    "filetest_simple\0",
     "forktest\0",
     "forktest2\0",
     "forktest_simple\0",
+    "fstattest\0",
     "hello_world\0",
+    "lazytest\0",
     "matrix\0",
+    "mmaptest\0",
+    "mprotecttest\0",
+    "nanosleeptest\0",
+    "pipeblocktest\0",
+    "seektest\0",
+    "sigtest\0",
     "sleep\0",
     "sleep_simple\0",
     "stack_overflow\0",
+    "stacktest\0",
+    "swaptest\0",
+    "threadtest\0",
+    "waittest\0",
     "yield\0",
 ];
 
@@ -26,7 +47,7 @@ pub fn main() -> i32 {
         println!("Usertests: Running {}", test);
         let pid = fork();
         if pid == 0 {
-            exec(*test);
+            exec(*test, &[*test]);
             panic!("unreachable!");
         } else {
             let mut exit_code: i32 = Default::default();
@@ -0,0 +1,47 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exit, fork, pipe, read, try_waitpid, wait, waitpid, write};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut exit_code: i32 = 0;
+    assert_eq!(try_waitpid(-1, &mut exit_code), -1);
+    // the child exits only after the parent has checked it is running
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let pid = fork();
+    if pid == 0 {
+        close(pipe_fd[1]);
+        let mut buffer = [0u8; 1];
+        assert_eq!(read(pipe_fd[0], &mut buffer), 1);
+        exit(buffer[0] as i32);
+    }
+    close(pipe_fd[0]);
+    assert_eq!(try_waitpid(pid, &mut exit_code), 0);
+    assert_eq!(try_waitpid(-1, &mut exit_code), 0);
+    assert_eq!(write(pipe_fd[1], &[7]), 1);
+    close(pipe_fd[1]);
+    // blocks until the child exits
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    assert_eq!(exit_code, 7);
+    assert_eq!(try_waitpid(pid, &mut exit_code), -1);
+    // any of several children wakes the parent up
+    for i in 0..4 {
+        if fork() == 0 {
+            exit(i);
+        }
+    }
+    let mut sum = 0;
+    for _ in 0..4 {
+        assert!(wait(&mut exit_code) > 0);
+        sum += exit_code;
+    }
+    assert_eq!(sum, 6);
+    assert_eq!(wait(&mut exit_code), -1);
+    println!("waittest passed!");
+    0
+}
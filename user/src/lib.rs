@@ -4,12 +4,18 @@
 #![feature(panic_info_message)]
 #![feature(alloc_error_handler)]
 
+#[macro_use]
+extern crate bitflags;
+extern crate alloc;
+
 #[macro_use]
 pub mod console;
 mod lang_items;
 mod syscall;
 
-use buddy_system_allocator::LockedHeap;
+use alloc::{boxed::Box, vec::Vec};
+use buddy_system_allocator::{Heap, LockedHeapWithRescue};
+use core::{alloc::Layout, ptr};
 use syscall::*;
 
 const USER_HEAP_SIZE: usize = 4096 * 4;
@@ -17,28 +23,54 @@ const USER_HEAP_SIZE: usize = 4096 * 4;
 static mut HEAP_SPACE: [u8; USER_HEAP_SIZE] = [0; USER_HEAP_SIZE];
 
 #[global_allocator]
-static HEAP_ALLOCATOR: LockedHeap<32> = LockedHeap::empty();
+static HEAP_ALLOCATOR: LockedHeapWithRescue<32> = LockedHeapWithRescue::new(extend_heap);
+
+/// Extend the heap with memory from `sbrk` when it runs out.
+fn extend_heap(heap: &mut Heap<32>, layout: &Layout) {
+    // twice the block size makes room for an aligned block
+    let size = layout.size().max(layout.align()).next_power_of_two() * 2;
+    let size = size.max(USER_HEAP_SIZE);
+    let start = sbrk(size as isize);
+    if start != -1 {
+        unsafe {
+            heap.add_to_heap(start as usize, start as usize + size);
+        }
+    }
+}
 
 #[alloc_error_handler]
 pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
     panic!("Heap allocation error, layout = {:?}", layout);
 }
 
+/// Address of the null-terminated envp array on the initial user stack.
+static mut ENVP: usize = 0;
+
+/// Read the null-terminated string at `ptr`.
+unsafe fn str_from_ptr(ptr: *const u8) -> &'static str {
+    let len = (0..).find(|&i| *ptr.add(i) == 0).unwrap();
+    core::str::from_utf8(core::slice::from_raw_parts(ptr, len)).unwrap()
+}
+
 #[no_mangle]
 #[link_section = ".text.entry"]
-pub extern "C" fn _start() -> ! {
+pub extern "C" fn _start(argc: usize, argv: usize, envp: usize) -> ! {
     unsafe {
         HEAP_ALLOCATOR
             .lock()
             .init(HEAP_SPACE.as_ptr() as usize, USER_HEAP_SIZE);
+        ENVP = envp;
     }
-    exit(main());
+    let args: Vec<&'static str> = (0..argc)
+        .map(|i| unsafe { str_from_ptr(*(argv as *const *const u8).add(i)) })
+        .collect();
+    exit(main(argc, args.as_slice()));
     panic!("Unreachable after sys_exit!");
 }
 
 #[no_mangle]
 #[linkage = "weak"]
-fn main() -> i32 {
+fn main(_argc: usize, _argv: &[&str]) -> i32 {
     panic!("Connot find main!");
 }
 
@@ -54,6 +86,212 @@ impl TimeVal {
     }
 }
 
+#[repr(C)]
+#[derive(Default)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+impl TimeSpec {
+    pub fn new() -> Self {
+        TimeSpec { sec: 0, nsec: 0 }
+    }
+}
+
+pub const AT_NULL: usize = 0;
+pub const AT_PHDR: usize = 3;
+pub const AT_PHENT: usize = 4;
+pub const AT_PHNUM: usize = 5;
+pub const AT_PAGESZ: usize = 6;
+pub const AT_ENTRY: usize = 9;
+pub const AT_RANDOM: usize = 25;
+
+/// Return all environment variables in the form of `NAME=VALUE`.
+pub fn environ() -> Vec<&'static str> {
+    let envp = unsafe { ENVP } as *const *const u8;
+    if envp.is_null() {
+        return Vec::new();
+    }
+    (0..)
+        .map(|i| unsafe { *envp.add(i) })
+        .take_while(|env| !env.is_null())
+        .map(|env| unsafe { str_from_ptr(env) })
+        .collect()
+}
+
+pub fn getenv(name: &str) -> Option<&'static str> {
+    environ().into_iter().find_map(|env| {
+        let mut iter = env.splitn(2, '=');
+        match (iter.next(), iter.next()) {
+            (Some(key), Some(value)) if key == name => Some(value),
+            _ => None,
+        }
+    })
+}
+
+/// Return the value of entry `type_` in the auxiliary vector, or 0 if there is not one.
+pub fn getauxval(type_: usize) -> usize {
+    let envp = unsafe { ENVP } as *const usize;
+    if envp.is_null() {
+        return 0;
+    }
+    unsafe {
+        // the auxiliary vector follows the null which ends envp
+        let mut auxv = envp.add((0..).find(|&i| *envp.add(i) == 0).unwrap() + 1);
+        while *auxv != AT_NULL {
+            if *auxv == type_ {
+                return *auxv.add(1);
+            }
+            auxv = auxv.add(2);
+        }
+    }
+    0
+}
+
+pub const AT_FDCWD: isize = -100;
+pub const AT_REMOVEDIR: u32 = 0x200;
+
+bitflags! {
+    pub struct OpenFlags: u32 {
+        const RDONLY = 0;
+        const WRONLY = 1 << 0;
+        const RDWR = 1 << 1;
+        const CREAT = 1 << 6;
+        const EXCL = 1 << 7;
+        const TRUNC = 1 << 9;
+        const APPEND = 1 << 10;
+        const DIRECTORY = 1 << 16;
+        const CLOEXEC = 1 << 19;
+    }
+}
+
+#[repr(C)]
+pub struct Stat {
+    pub dev: u64,
+    pub ino: u64,
+    pub mode: StatMode,
+    pub nlink: u32,
+    pub size: u64,
+}
+
+impl Stat {
+    pub fn new() -> Self {
+        Stat {
+            dev: 0,
+            ino: 0,
+            mode: StatMode::empty(),
+            nlink: 0,
+            size: 0,
+        }
+    }
+}
+
+bitflags! {
+    pub struct StatMode: u32 {
+        const FIFO = 0o010000;
+        const CHR = 0o020000;
+        const DIR = 0o040000;
+        const FILE = 0o100000;
+    }
+}
+
+pub const SEEK_SET: usize = 0;
+pub const SEEK_CUR: usize = 1;
+pub const SEEK_END: usize = 2;
+
+pub const DT_DIR: u8 = 4;
+pub const DT_REG: u8 = 8;
+
+/// An entry parsed from `linux_dirent64` records.
+pub struct Dirent<'a> {
+    pub ino: u64,
+    pub type_: u8,
+    pub name: &'a str,
+}
+
+/// Parse the records filled by `getdents`.
+pub fn parse_dirents(buf: &[u8]) -> Vec<Dirent> {
+    let mut v = Vec::new();
+    let mut pos = 0;
+    while pos < buf.len() {
+        let record = &buf[pos..];
+        let mut ino = [0u8; 8];
+        ino.copy_from_slice(&record[..8]);
+        let reclen = record[16] as usize | (record[17] as usize) << 8;
+        let name = &record[19..reclen];
+        let name_len = name.iter().position(|&b| b == 0).unwrap();
+        v.push(Dirent {
+            ino: u64::from_le_bytes(ino),
+            type_: record[18],
+            name: core::str::from_utf8(&name[..name_len]).unwrap(),
+        });
+        pos += reclen;
+    }
+    v
+}
+
+pub fn open(path: &str, flags: OpenFlags) -> isize {
+    sys_openat(AT_FDCWD, path, flags.bits)
+}
+
+pub fn openat(dirfd: isize, path: &str, flags: OpenFlags) -> isize {
+    sys_openat(dirfd, path, flags.bits)
+}
+
+/// Return the length of the path written into `buffer`, including the ending '\0'.
+pub fn getcwd(buffer: &mut [u8]) -> isize {
+    sys_getcwd(buffer)
+}
+
+pub fn chdir(path: &str) -> isize {
+    sys_chdir(path)
+}
+
+pub fn mkdir(path: &str) -> isize {
+    sys_mkdirat(AT_FDCWD, path, 0)
+}
+
+pub fn unlink(path: &str) -> isize {
+    sys_unlinkat(AT_FDCWD, path, 0)
+}
+
+pub fn rmdir(path: &str) -> isize {
+    sys_unlinkat(AT_FDCWD, path, AT_REMOVEDIR)
+}
+
+pub fn link(old_path: &str, new_path: &str) -> isize {
+    sys_linkat(AT_FDCWD, old_path, AT_FDCWD, new_path, 0)
+}
+
+pub fn getdents(fd: usize, buffer: &mut [u8]) -> isize {
+    sys_getdents64(fd, buffer)
+}
+
+pub fn lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    sys_lseek(fd, offset, whence)
+}
+
+pub fn pread(fd: usize, buffer: &mut [u8], offset: usize) -> isize {
+    sys_pread64(fd, buffer, offset)
+}
+
+pub fn pwrite(fd: usize, buffer: &[u8], offset: usize) -> isize {
+    sys_pwrite64(fd, buffer, offset)
+}
+
+pub fn fstat(fd: usize, st: &mut Stat) -> isize {
+    sys_fstat(fd, st)
+}
+
+pub fn dup(fd: usize) -> isize {
+    sys_dup(fd)
+}
+
+pub fn dup3(old_fd: usize, new_fd: usize, flags: OpenFlags) -> isize {
+    sys_dup3(old_fd, new_fd, flags.bits)
+}
+
 pub fn close(fd: usize) -> isize {
     sys_close(fd)
 }
@@ -70,6 +308,7 @@ pub fn write(fd: usize, buffer: &[u8]) -> isize {
     sys_write(fd, buffer)
 }
 
+/// Exit the calling thread, which exits the whole process if it is the main thread.
 pub fn exit(exit_code: i32) -> isize {
     sys_exit(exit_code)
 }
@@ -95,33 +334,304 @@ pub fn getpid() -> isize {
     sys_getpid()
 }
 
+/// Only the calling thread is copied, which becomes the main thread of the child.
 pub fn fork() -> isize {
     sys_fork()
 }
 
-pub fn exec(path: &str) -> isize {
-    sys_exec(path)
+/// Id of the calling thread in its process, 0 for the main thread.
+pub fn gettid() -> isize {
+    sys_gettid()
+}
+
+/// New threads start here with the boxed entry and argument.
+extern "C" fn thread_start(start: *mut (fn(usize) -> i32, usize)) -> ! {
+    let (entry, arg) = *unsafe { Box::from_raw(start) };
+    exit(entry(arg));
+    panic!("Unreachable after sys_exit!");
+}
+
+/// Run `entry(arg)` in a new thread of the process, whose return value is the exit code
+/// of the thread, return its tid.
+pub fn thread_create(entry: fn(usize) -> i32, arg: usize) -> isize {
+    let start = Box::into_raw(Box::new((entry, arg)));
+    let tid = sys_thread_create(thread_start as usize, start as usize);
+    if tid < 0 {
+        drop(unsafe { Box::from_raw(start) });
+    }
+    tid
+}
+
+/// Wait for thread `tid` to exit and get its exit code, return `tid`.
+pub fn waittid(tid: usize, exit_code: &mut i32) -> isize {
+    sys_waittid(tid, exit_code)
+}
+
+pub const PROT_READ: usize = 0x1;
+pub const PROT_WRITE: usize = 0x2;
+pub const PROT_EXEC: usize = 0x4;
+pub const PROT_NONE: usize = 0;
+pub const MAP_SHARED: usize = 0x01;
+pub const MAP_PRIVATE: usize = 0x02;
+pub const MAP_FIXED: usize = 0x10;
+pub const MAP_ANONYMOUS: usize = 0x20;
+pub const MS_ASYNC: usize = 1;
+pub const MS_INVALIDATE: usize = 2;
+pub const MS_SYNC: usize = 4;
+
+/// Return the start address of the mapping, or a negative errno.
+/// `start` is only a hint without `MAP_FIXED`, and the kernel chooses one if it is 0.
+pub fn mmap(
+    start: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    fd: isize,
+    offset: usize,
+) -> isize {
+    sys_mmap(start, len, prot, flags, fd, offset)
+}
+
+/// Unmap pages in the range, which may cover parts of mappings.
+pub fn munmap(start: usize, len: usize) -> isize {
+    sys_munmap(start, len)
+}
+
+/// Return 0 on success, or a negative errno if any page in the range is not mapped.
+pub fn mprotect(start: usize, len: usize, prot: usize) -> isize {
+    sys_mprotect(start, len, prot)
+}
+
+/// Write back pages of shared file mappings in the range.
+pub fn msync(start: usize, len: usize, flags: usize) -> isize {
+    sys_msync(start, len, flags)
+}
+
+/// Return the new program break, which is unchanged on failure.
+pub fn brk(addr: usize) -> isize {
+    sys_brk(addr)
+}
+
+/// Move the program break by `increment`, return the old one, or -1 on failure.
+pub fn sbrk(increment: isize) -> isize {
+    let old = sys_brk(0);
+    let new = old + increment;
+    if sys_brk(new as usize) != new {
+        return -1;
+    }
+    old
+}
+
+/// Turn strings ending with '\0' into a null-terminated array of pointers.
+fn to_ptr_array(strs: &[&str]) -> Vec<*const u8> {
+    let mut v: Vec<*const u8> = strs.iter().map(|s| s.as_ptr()).collect();
+    v.push(ptr::null());
+    v
+}
+
+/// `path` and each of `args` and `envs` should end with '\0',
+/// `args[0]` is the program name by convention.
+pub fn execve(path: &str, args: &[&str], envs: &[&str]) -> isize {
+    sys_execve(path, &to_ptr_array(args), &to_ptr_array(envs))
+}
+
+/// Same as `execve`, with the environment of current process.
+pub fn exec(path: &str, args: &[&str]) -> isize {
+    // strings of environ() are followed by '\0' on the user stack
+    execve(path, args, &environ())
+}
+
+/// Run the program at `path` in a new child process with the environment of
+/// current process, return its pid.
+pub fn spawn(path: &str, args: &[&str]) -> isize {
+    sys_spawn(path, &to_ptr_array(args), &to_ptr_array(&environ()))
 }
 
 pub fn wait(exit_code: &mut i32) -> isize {
     waitpid(-1, exit_code)
 }
 
+pub const WNOHANG: usize = 1;
+
+/// Block until the child exits, return -1 if there is no such child.
 pub fn waitpid(pid: isize, exit_code: &mut i32) -> isize {
-    loop {
-        match sys_waitpid(pid, exit_code as *mut _) {
-            -2 => {
-                yield_();
-            }
-            // -1 or a real pid
-            exit_pid => return exit_pid,
+    sys_waitpid(pid, exit_code as *mut _, 0)
+}
+
+/// Return 0 at once if the child is still running.
+pub fn try_waitpid(pid: isize, exit_code: &mut i32) -> isize {
+    sys_waitpid(pid, exit_code as *mut _, WNOHANG)
+}
+
+/// Block for `req`, return -22 if the nanoseconds are out of range, or -4 with the time
+/// left in `rem` if a signal handler interrupts it.
+pub fn nanosleep(req: &TimeSpec, rem: &mut TimeSpec) -> isize {
+    sys_nanosleep(req, rem)
+}
+
+pub fn sleep(period_ms: usize) {
+    let req = TimeSpec {
+        sec: period_ms / 1000,
+        nsec: period_ms % 1000 * 1000000,
+    };
+    nanosleep(&req, &mut TimeSpec::new());
+}
+
+pub const SIGHUP: i32 = 1;
+pub const SIGINT: i32 = 2;
+pub const SIGQUIT: i32 = 3;
+pub const SIGILL: i32 = 4;
+pub const SIGTRAP: i32 = 5;
+pub const SIGABRT: i32 = 6;
+pub const SIGBUS: i32 = 7;
+pub const SIGFPE: i32 = 8;
+pub const SIGKILL: i32 = 9;
+pub const SIGUSR1: i32 = 10;
+pub const SIGSEGV: i32 = 11;
+pub const SIGUSR2: i32 = 12;
+pub const SIGPIPE: i32 = 13;
+pub const SIGALRM: i32 = 14;
+pub const SIGTERM: i32 = 15;
+pub const SIGSTKFLT: i32 = 16;
+pub const SIGCHLD: i32 = 17;
+pub const SIGCONT: i32 = 18;
+pub const SIGSTOP: i32 = 19;
+pub const SIGTSTP: i32 = 20;
+pub const SIGTTIN: i32 = 21;
+pub const SIGTTOU: i32 = 22;
+pub const SIGURG: i32 = 23;
+pub const SIGXCPU: i32 = 24;
+pub const SIGXFSZ: i32 = 25;
+pub const SIGVTALRM: i32 = 26;
+pub const SIGPROF: i32 = 27;
+pub const SIGWINCH: i32 = 28;
+pub const SIGIO: i32 = 29;
+pub const SIGPWR: i32 = 30;
+pub const SIGSYS: i32 = 31;
+
+bitflags! {
+    /// A set of signals, where bit `n` stands for signal `n`.
+    pub struct SignalFlags: u32 {
+        const SIGHUP = 1 << 1;
+        const SIGINT = 1 << 2;
+        const SIGQUIT = 1 << 3;
+        const SIGILL = 1 << 4;
+        const SIGTRAP = 1 << 5;
+        const SIGABRT = 1 << 6;
+        const SIGBUS = 1 << 7;
+        const SIGFPE = 1 << 8;
+        const SIGKILL = 1 << 9;
+        const SIGUSR1 = 1 << 10;
+        const SIGSEGV = 1 << 11;
+        const SIGUSR2 = 1 << 12;
+        const SIGPIPE = 1 << 13;
+        const SIGALRM = 1 << 14;
+        const SIGTERM = 1 << 15;
+        const SIGSTKFLT = 1 << 16;
+        const SIGCHLD = 1 << 17;
+        const SIGCONT = 1 << 18;
+        const SIGSTOP = 1 << 19;
+        const SIGTSTP = 1 << 20;
+        const SIGTTIN = 1 << 21;
+        const SIGTTOU = 1 << 22;
+        const SIGURG = 1 << 23;
+        const SIGXCPU = 1 << 24;
+        const SIGXFSZ = 1 << 25;
+        const SIGVTALRM = 1 << 26;
+        const SIGPROF = 1 << 27;
+        const SIGWINCH = 1 << 28;
+        const SIGIO = 1 << 29;
+        const SIGPWR = 1 << 30;
+        const SIGSYS = 1 << 31;
+    }
+}
+
+/// Handlers which take the default action, or ignore the signal.
+pub const SIG_DFL: usize = 0;
+pub const SIG_IGN: usize = 1;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SignalAction {
+    /// `SIG_DFL`, `SIG_IGN` or the address of an `extern "C" fn(i32)`.
+    pub handler: usize,
+    /// Signals blocked in addition while the handler is running.
+    pub mask: SignalFlags,
+    /// Filled by `sigaction`.
+    pub restorer: usize,
+}
+
+impl SignalAction {
+    pub fn new(handler: usize, mask: SignalFlags) -> Self {
+        SignalAction {
+            handler,
+            mask,
+            restorer: 0,
         }
     }
 }
 
-pub fn sleep(period_ms: usize) {
-    let start = get_time();
-    while get_time() < start + period_ms as isize {
-        yield_();
+impl Default for SignalAction {
+    fn default() -> Self {
+        SignalAction::new(SIG_DFL, SignalFlags::empty())
+    }
+}
+
+/// Send signal `signum` to process `pid`, signal 0 only checks whether it exists.
+pub fn kill(pid: isize, signum: i32) -> isize {
+    sys_kill(pid, signum)
+}
+
+/// Handlers return here, which never returns to them.
+extern "C" fn sigreturn_trampoline() -> ! {
+    sigreturn();
+    panic!("Unreachable after sys_sigreturn!");
+}
+
+/// Set the action of `signum` if `action` is given, and get the old one.
+pub fn sigaction(
+    signum: i32,
+    action: Option<&SignalAction>,
+    old_action: Option<&mut SignalAction>,
+) -> isize {
+    let action = action.map(|action| SignalAction {
+        restorer: sigreturn_trampoline as usize,
+        ..*action
+    });
+    sys_sigaction(
+        signum,
+        action
+            .as_ref()
+            .map_or(ptr::null(), |action| action as *const _),
+        old_action.map_or(ptr::null_mut(), |action| action as *mut _),
+    )
+}
+
+pub const SIG_BLOCK: usize = 0;
+pub const SIG_UNBLOCK: usize = 1;
+pub const SIG_SETMASK: usize = 2;
+
+/// Change the blocked signals if `set` is given, and get the old ones.
+pub fn sigprocmask(
+    how: usize,
+    set: Option<SignalFlags>,
+    old_set: Option<&mut SignalFlags>,
+) -> isize {
+    let set = set.map(|set| set.bits());
+    let mut old = 0;
+    let ret = sys_sigprocmask(
+        how,
+        set.as_ref().map_or(ptr::null(), |set| set as *const _),
+        &mut old,
+    );
+    match old_set {
+        Some(old_set) if ret == 0 => *old_set = SignalFlags::from_bits_truncate(old),
+        _ => {}
     }
+    ret
+}
+
+/// Return from a signal handler, which is done by the restorer `sigaction` sets up.
+pub fn sigreturn() -> isize {
+    sys_sigreturn()
 }